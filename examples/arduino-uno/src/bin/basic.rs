@@ -0,0 +1,125 @@
+//! A basic example driving a two-motor robot with the L298N H-Bridge rust driver on an
+//! Arduino Uno (ATmega328P), using Timer1 for the enable PWM. The Nano is wired the same.
+//!
+//! A robot running this example is expected to slowly ramp up driving forward, before
+//! stopping, then spinning around on the spot.
+//!
+//! # 8-bit duty
+//! Timer1 is used in its 8-bit fast PWM mode, so the enable has only 256 duty steps. The
+//! driver scales its 16-bit throttle onto them, rounding, so one duty step is 257 throttle
+//! counts and throttles below 129 are fully off. Throttles are best chosen as multiples of
+//! [`DUTY_STEP`], see [`throttle_for_duty`].
+//!
+//! # Wiring
+//! The L298N should be wired to the Arduino's pins using the following mappings. Both
+//! enables are on Timer1, so they share one PWM frequency.
+//! =============================
+//! |   L298N   |   Arduino     |
+//! |-----------|---------------|
+//! |   En A    |   D9  (OC1A)  |
+//! |   I1      |   D7          |
+//! |   I2      |   D8          |
+//! |   En B    |   D10 (OC1B)  |
+//! |   I3      |   D11         |
+//! |   I4      |   D12         |
+//! =============================
+
+#![no_main]
+#![no_std]
+
+use core::convert::Infallible;
+
+use arduino_hal::port::{mode::PwmOutput, Pin};
+use arduino_hal::simple_pwm::{IntoPwmPin, Prescaler, PwmPinOps, Timer1Pwm};
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use panic_halt as _;
+
+use l298_hbridge::{L298NHBridge, DifferentialDrive, Maneuver, CommandScheduler, StopMode};
+
+/// Throttle counts per 8-bit duty step
+const DUTY_STEP: u16 = u16::MAX / 255;
+
+/// The throttle giving exactly `duty` out of 255
+const fn throttle_for_duty(duty: u8) -> u16 {
+    duty as u16 * DUTY_STEP
+}
+
+/// Adapts an avr-hal PWM pin to the driver's 16-bit duty cycle interface.
+///
+/// The duty requested by the driver never exceeds [`SetDutyCycle::max_duty_cycle`], which
+/// is the timer's 8-bit maximum, so it always fits the pin's `u8` duty.
+struct Pwm8<TC, PIN: PwmPinOps<TC>>(Pin<PwmOutput<TC>, PIN>);
+
+impl<TC, PIN: PwmPinOps<TC>> ErrorType for Pwm8<TC, PIN> {
+    type Error = Infallible;
+}
+
+impl<TC, PIN: PwmPinOps<TC>> SetDutyCycle for Pwm8<TC, PIN> {
+    fn max_duty_cycle(&self) -> u16 {
+        u16::from(self.0.get_max_duty())
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        self.0.set_duty(u8::try_from(duty).unwrap_or(u8::MAX));
+        Ok(())
+    }
+}
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    // === Periperal setup ==================================================
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    // Setup PWM, 16 MHz / 8 / 256 = 7.8 kHz. This is audible, but no prescaler gives
+    // 62.5 kHz, above the L298's recommended maximum.
+    let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale8);
+
+    let mut left_enable = pins.d9.into_output().into_pwm(&timer1);
+    left_enable.enable();
+
+    let mut right_enable = pins.d10.into_output().into_pwm(&timer1);
+    right_enable.enable();
+
+    // === L298N setup ======================================================
+    let left_motor = L298NHBridge::new(
+        pins.d7.into_output(),
+        pins.d8.into_output(),
+        Pwm8(left_enable),
+    )
+    .unwrap();
+
+    let right_motor = L298NHBridge::new(
+        pins.d11.into_output(),
+        pins.d12.into_output(),
+        Pwm8(right_enable),
+    )
+    .unwrap();
+
+    let mut drive = DifferentialDrive::new(left_motor, right_motor);
+
+    arduino_hal::delay_ms(1000);
+
+    // === Program Logic ====================================================
+    let maneuver = Maneuver::<12>::new()
+        .forward(throttle_for_duty(96), 1000)
+        .forward(throttle_for_duty(160), 1000)
+        .forward(throttle_for_duty(255), 1000)
+        .coast(2000)
+        .spin_right(throttle_for_duty(216), 2000)
+        .end_with(StopMode::Brake);
+
+    let mut scheduler = CommandScheduler::new(maneuver.sequence().unwrap());
+
+    // Counted by the loop below, there is no free running millisecond timer in avr-hal
+    let mut now_ms: u32 = 0;
+    scheduler.start(&now_ms);
+
+    while !scheduler.poll(&mut drive, &now_ms).unwrap() {
+        arduino_hal::delay_ms(1);
+        now_ms = now_ms.wrapping_add(1);
+    }
+
+    // Loop forever
+    loop { }
+}
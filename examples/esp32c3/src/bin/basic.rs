@@ -0,0 +1,126 @@
+//! A basic example driving a two-motor robot with the L298N H-Bridge rust driver on an
+//! ESP32-C3, using esp-hal's LEDC peripheral for the enable PWM.
+//!
+//! A robot running this example is expected to initially drive forward,
+//! before stopping, then spinning around on the spot.
+//!
+//! # Wiring
+//! The L298N should be wired to the ESP32-C3's GPIOs using the following mappings. Both
+//! enables share LEDC timer 0 at 20 kHz.
+//! =============================
+//! |   L298N   |   ESP32-C3    |
+//! |-----------|---------------|
+//! |   En A    |   GPIO0       |
+//! |   I1      |   GPIO1       |
+//! |   I2      |   GPIO2       |
+//! |   En B    |   GPIO3       |
+//! |   I3      |   GPIO4       |
+//! |   I4      |   GPIO5       |
+//! =============================
+
+#![no_main]
+#![no_std]
+
+use core::convert::Infallible;
+
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use esp_backtrace as _;
+use esp_hal::delay::Delay;
+use esp_hal::gpio::{Level, Output};
+use esp_hal::ledc::channel::{self, Channel, ChannelIFace};
+use esp_hal::ledc::timer::{self, TimerIFace};
+use esp_hal::ledc::{LSGlobalClkSource, Ledc, LowSpeed};
+use esp_hal::prelude::*;
+
+use l298_hbridge::{L298NHBridge, DifferentialDrive, Maneuver, CommandScheduler, StopMode};
+
+/// Adapts an LEDC channel to the driver, which expects infallible PWM outputs.
+///
+/// LEDC only rejects duty cycles above the maximum, which the driver never requests.
+struct LedcPwm<'a>(Channel<'a, LowSpeed>);
+
+impl ErrorType for LedcPwm<'_> {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for LedcPwm<'_> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.0.max_duty_cycle()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        self.0.set_duty_cycle(duty).unwrap();
+        Ok(())
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    // === Periperal setup ==================================================
+    let peripherals = esp_hal::init(esp_hal::Config::default());
+
+    // Setup PWM
+    let mut ledc = Ledc::new(peripherals.LEDC);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let mut pwm_timer = ledc.timer::<LowSpeed>(timer::Number::Timer0);
+    pwm_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 20.kHz(),
+        })
+        .unwrap();
+
+    let channel_config = || channel::config::Config {
+        timer: &pwm_timer,
+        duty_pct: 0,
+        pin_config: channel::config::PinConfig::PushPull,
+    };
+
+    let mut left_enable = ledc.channel(channel::Number::Channel0, peripherals.GPIO0);
+    left_enable.configure(channel_config()).unwrap();
+
+    let mut right_enable = ledc.channel(channel::Number::Channel1, peripherals.GPIO3);
+    right_enable.configure(channel_config()).unwrap();
+
+    // === L298N setup ======================================================
+    let left_motor = L298NHBridge::new(
+        Output::new(peripherals.GPIO1, Level::Low),
+        Output::new(peripherals.GPIO2, Level::Low),
+        LedcPwm(left_enable),
+    )
+    .unwrap();
+
+    let right_motor = L298NHBridge::new(
+        Output::new(peripherals.GPIO4, Level::Low),
+        Output::new(peripherals.GPIO5, Level::Low),
+        LedcPwm(right_enable),
+    )
+    .unwrap();
+
+    let mut drive = DifferentialDrive::new(left_motor, right_motor);
+
+    let delay = Delay::new();
+    delay.delay_millis(1000);
+
+    // === Program Logic ====================================================
+    let maneuver = Maneuver::<10>::new()
+        .forward(u16::MAX, 2000)
+        .coast(2000)
+        .spin_right(55000, 2000)
+        .spin_left(55000, 2000)
+        .end_with(StopMode::Brake);
+
+    let mut scheduler = CommandScheduler::new(maneuver.sequence().unwrap());
+
+    let now_ms = || esp_hal::time::now().duration_since_epoch().to_millis() as u32;
+    scheduler.start(&now_ms);
+
+    while !scheduler.poll(&mut drive, &now_ms).unwrap() {
+        delay.delay_millis(1);
+    }
+
+    // Loop forever
+    loop { }
+}
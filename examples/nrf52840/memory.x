@@ -0,0 +1,12 @@
+MEMORY
+{
+  /* NOTE K = KiBi = 1024 bytes */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 1024K
+  RAM : ORIGIN = 0x20000000, LENGTH = 256K
+
+  /* With the S140 v7 SoftDevice flashed, the application starts after it instead, and
+     RAM must leave room for the SoftDevice's configured RAM use:
+  FLASH : ORIGIN = 0x00027000, LENGTH = 1024K - 0x27000
+  RAM : ORIGIN = 0x20020000, LENGTH = 128K
+  */
+}
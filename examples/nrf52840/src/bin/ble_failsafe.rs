@@ -0,0 +1,150 @@
+//! Drives a motor on an nRF52840 from commands received over BLE, through a
+//! `CommandWatchdog` failsafe.
+//!
+//! The BLE link is stubbed out by `ble_receive`, which plays back a few commands and then
+//! goes silent, as a dropped connection would. The watchdog then coasts the motor and stops
+//! feeding the WDT, so the MCU resets and the demo starts over.
+//!
+//! # SoftDevice
+//! Nothing here uses peripherals reserved by the SoftDevice (TIMER0, RTC0, RADIO, ...): the
+//! PWM is PWM0 and delays use TIMER1, so a real BLE stack can be added alongside. See
+//! `memory.x` for the memory layout to use with the S140 flashed.
+//!
+//! # Wiring
+//! The L298N should be wired to the nRF52840's GPIOs using the following mappings.
+//! =============================
+//! |   L298N   |   nRF52840    |
+//! |-----------|---------------|
+//! |   En A    |   P0.02       |
+//! |   I1      |   P0.03       |
+//! |   I2      |   P0.04       |
+//! =============================
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use core::convert::Infallible;
+
+use cortex_m_rt::entry;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use nrf52840_hal::{self as hal, pac};
+use nrf52840_hal::gpio::{p0, Level};
+use nrf52840_hal::pwm::{self, Pwm};
+use nrf52840_hal::time::Hertz;
+use nrf52840_hal::timer::Timer;
+use nrf52840_hal::wdt::{self, Watchdog, WatchdogHandle};
+use panic_halt as _;
+
+use l298_hbridge::{L298NHBridge, CommandWatchdog, WatchdogFeed, Command, Direction, StopMode};
+
+/// Commands are expected at least this often, or the motor coasts
+const COMMAND_TIMEOUT_MS: u32 = 250;
+
+/// WDT period in 32.768 kHz LFCLK ticks, 1 s
+const WDT_TICKS: u32 = 32_768;
+
+/// Adapts one channel of the nRF PWM peripheral to the driver.
+///
+/// The nRF PWM counts its compare value as the time the output is *low* by default, so a
+/// raw duty of 0 would be full on. `set_duty_on` corrects for this, so the driver's duty
+/// cycle is the time the enable pin is high, as on other HALs.
+struct NrfPwmChannel<'a, T: pwm::Instance> {
+    pwm: &'a Pwm<T>,
+    channel: pwm::Channel,
+}
+
+impl<T: pwm::Instance> ErrorType for NrfPwmChannel<'_, T> {
+    type Error = Infallible;
+}
+
+impl<T: pwm::Instance> SetDutyCycle for NrfPwmChannel<'_, T> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.pwm.max_duty()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        self.pwm.set_duty_on(self.channel, duty);
+        Ok(())
+    }
+}
+
+/// Feeds the WDT through its one reload request handle
+struct WdtFeed(WatchdogHandle<wdt::handles::Hdl0>);
+
+impl WatchdogFeed for WdtFeed {
+    fn feed(&mut self) {
+        self.0.pet();
+    }
+}
+
+/// Stand-in for the BLE stack, returning the command received at `now_ms`, if any.
+///
+/// Drives forward, then reverses, with a command every 100 ms, then stops sending as a
+/// dropped connection would.
+fn ble_receive(now_ms: u32) -> Option<Command> {
+    if now_ms % 100 != 0 {
+        return None;
+    }
+
+    match now_ms {
+        0..2000 => Some(Command::Drive { direction: Direction::Forward, throttle: 40000 }),
+        2000..2500 => Some(Command::Stop(StopMode::Brake)),
+        2500..4500 => Some(Command::Drive { direction: Direction::Reverse, throttle: 40000 }),
+        _ => None,
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    // === Periperal setup ==================================================
+    let pac = pac::Peripherals::take().unwrap();
+
+    // The WDT runs from the LFCLK
+    hal::clocks::Clocks::new(pac.CLOCK).start_lfclk();
+
+    let port0 = p0::Parts::new(pac.P0);
+
+    // Setup PWM
+    let pwm = Pwm::new(pac.PWM0);
+    pwm.set_output_pin(pwm::Channel::C0, port0.p0_02.into_push_pull_output(Level::Low).degrade());
+    pwm.set_period(Hertz(20_000));
+
+    let enable = NrfPwmChannel { pwm: &pwm, channel: pwm::Channel::C0 };
+
+    // Setup watchdog
+    let mut watchdog = match Watchdog::try_new(pac.WDT) {
+        Ok(watchdog) => watchdog,
+        // Already running from before a reset, it can't be reconfigured
+        Err(_) => panic!(),
+    };
+    watchdog.set_lfosc_ticks(WDT_TICKS);
+    let wdt::Parts { watchdog: _watchdog, handles } = watchdog.activate::<wdt::count::One>();
+
+    let mut timer = Timer::new(pac.TIMER1);
+
+    // === L298N setup ======================================================
+    let motor = L298NHBridge::new(
+        port0.p0_03.into_push_pull_output(Level::Low),
+        port0.p0_04.into_push_pull_output(Level::Low),
+        enable,
+    )
+    .unwrap();
+
+    let mut motor = CommandWatchdog::new(motor, WdtFeed(handles.0), COMMAND_TIMEOUT_MS);
+
+    // === Program Logic ====================================================
+    let mut now_ms: u32 = 0;
+
+    loop {
+        if let Some(cmd) = ble_receive(now_ms) {
+            motor.set(cmd, &now_ms).unwrap();
+        }
+
+        motor.poll(&now_ms).unwrap();
+
+        timer.delay_ms(1);
+        now_ms = now_ms.wrapping_add(1);
+    }
+}
@@ -0,0 +1,130 @@
+//! A basic example driving a two-motor robot with the L298N H-Bridge rust driver from a
+//! Raspberry Pi running Linux, with the direction pins on the GPIO character device and
+//! the enables on the hardware PWM through sysfs.
+//!
+//! A robot running this example is expected to initially drive forward,
+//! before stopping, then spinning around on the spot.
+//!
+//! # Setup
+//! Both PWM channels must be enabled with `dtoverlay=pwm-2chan` in `/boot/config.txt`,
+//! which puts channel 0 on GPIO18 and channel 1 on GPIO19. Build and run on the Pi itself
+//! with `cargo run --bin basic`, as a user in the `gpio` group.
+//!
+//! # Wiring
+//! The L298N should be wired to the Pi's GPIOs (BCM numbering) using the following
+//! mappings.
+//! =============================
+//! |   L298N   |   Raspberry Pi |
+//! |-----------|----------------|
+//! |   En A    |   GPIO18 (PWM0)|
+//! |   I1      |   GPIO23       |
+//! |   I2      |   GPIO24       |
+//! |   En B    |   GPIO19 (PWM1)|
+//! |   I3      |   GPIO5        |
+//! |   I4      |   GPIO6        |
+//! =============================
+
+use std::convert::Infallible;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use embedded_hal::digital::{ErrorType as PinErrorType, OutputPin};
+use embedded_hal::pwm::{ErrorType as PwmErrorType, SetDutyCycle};
+use linux_embedded_hal::gpio_cdev::{Chip, LineRequestFlags};
+use linux_embedded_hal::CdevPin;
+use sysfs_pwm::Pwm;
+
+use l298_hbridge::{L298NHBridge, DifferentialDrive, Maneuver, CommandScheduler, StopMode};
+
+/// 20 kHz
+const PWM_PERIOD_NS: u32 = 50_000;
+
+/// The driver's pins can't fail, but writes through the kernel can. Losing control of a
+/// motor isn't recoverable here, so a failed write panics.
+struct Gpio(CdevPin);
+
+impl PinErrorType for Gpio {
+    type Error = Infallible;
+}
+
+impl OutputPin for Gpio {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set_low().expect("GPIO write failed");
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set_high().expect("GPIO write failed");
+        Ok(())
+    }
+}
+
+/// A sysfs PWM channel with a fixed period, taking its duty cycle at full 16-bit
+/// resolution.
+struct SysfsPwm(Pwm);
+
+impl PwmErrorType for SysfsPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for SysfsPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        let duty_ns = u64::from(PWM_PERIOD_NS) * u64::from(duty) / u64::from(u16::MAX);
+        self.0.set_duty_cycle_ns(duty_ns as u32).expect("PWM write failed");
+        Ok(())
+    }
+}
+
+fn output(chip: &mut Chip, line: u32) -> Gpio {
+    let handle = chip
+        .get_line(line)
+        .and_then(|line| line.request(LineRequestFlags::OUTPUT, 0, "l298n"))
+        .expect("failed to request GPIO line");
+
+    Gpio(CdevPin::new(handle).expect("failed to configure GPIO line"))
+}
+
+fn pwm(channel: u32) -> SysfsPwm {
+    let pwm = Pwm::new(0, channel).expect("failed to open PWM channel");
+    pwm.export().expect("failed to export PWM channel");
+    pwm.set_period_ns(PWM_PERIOD_NS).expect("failed to set PWM period");
+    pwm.set_duty_cycle_ns(0).expect("failed to set PWM duty cycle");
+    pwm.enable(true).expect("failed to enable PWM channel");
+
+    SysfsPwm(pwm)
+}
+
+fn main() {
+    // === Periperal setup ==================================================
+    let mut chip = Chip::new("/dev/gpiochip0").expect("failed to open GPIO chip");
+
+    // === L298N setup ======================================================
+    let left_motor = L298NHBridge::new(output(&mut chip, 23), output(&mut chip, 24), pwm(0)).unwrap();
+    let right_motor = L298NHBridge::new(output(&mut chip, 5), output(&mut chip, 6), pwm(1)).unwrap();
+
+    let mut drive = DifferentialDrive::new(left_motor, right_motor);
+
+    thread::sleep(Duration::from_secs(1));
+
+    // === Program Logic ====================================================
+    let maneuver = Maneuver::<10>::new()
+        .forward(u16::MAX, 2000)
+        .coast(2000)
+        .spin_right(55000, 2000)
+        .spin_left(55000, 2000)
+        .end_with(StopMode::Brake);
+
+    let mut scheduler = CommandScheduler::new(maneuver.sequence().unwrap());
+
+    let start = Instant::now();
+    let now_ms = || start.elapsed().as_millis() as u32;
+    scheduler.start(&now_ms);
+
+    while !scheduler.poll(&mut drive, &now_ms).unwrap() {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
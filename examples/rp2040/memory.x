@@ -0,0 +1,18 @@
+MEMORY
+{
+  /* NOTE K = KiBi = 1024 bytes */
+  BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+  FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+  RAM   : ORIGIN = 0x20000000, LENGTH = 256K
+}
+
+/* The second stage bootloader, provided by the rp2040-boot2 crate, must be at the very start
+   of flash */
+EXTERN(BOOT2_FIRMWARE)
+
+SECTIONS {
+  .boot2 ORIGIN(BOOT2) :
+  {
+    KEEP(*(.boot2));
+  } > BOOT2
+} INSERT BEFORE .text;
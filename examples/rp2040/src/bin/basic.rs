@@ -0,0 +1,99 @@
+//! A basic example driving a two-motor robot with the L298N H-Bridge rust driver on a
+//! Raspberry Pi Pico (RP2040).
+//!
+//! A robot running this example is expected to initially drive forward,
+//! before stopping, then spinning around on the spot.
+//!
+//! # Wiring
+//! The L298N should be wired to the Pico's GPIOs using the following mappings. Both enables
+//! are on PWM slice 0, so they share one 20 kHz PWM frequency.
+//! =============================
+//! |   L298N   |   Pico        |
+//! |-----------|---------------|
+//! |   En A    |   GP0 (PWM0A) |
+//! |   I1      |   GP2         |
+//! |   I2      |   GP3         |
+//! |   En B    |   GP1 (PWM0B) |
+//! |   I3      |   GP4         |
+//! |   I4      |   GP5         |
+//! =============================
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use embedded_hal::delay::DelayNs;
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal::{self, pac};
+
+use l298_hbridge::{differential_drive, Maneuver, CommandScheduler, StopMode};
+
+/// PWM counter wrap for 20 kHz from the 125 MHz system clock
+const PWM_TOP: u16 = 6249;
+
+#[entry]
+fn main() -> ! {
+    // === Periperal setup ==================================================
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // Configure GPIO
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = rp_pico::Pins::new(pac.IO_BANK0, pac.PADS_BANK0, sio.gpio_bank0, &mut pac.RESETS);
+
+    // Setup PWM
+    let pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let mut pwm = pwm_slices.pwm0;
+    pwm.set_top(PWM_TOP);
+    pwm.enable();
+
+    let mut left_enable = pwm.channel_a;
+    left_enable.output_to(pins.gpio0);
+
+    let mut right_enable = pwm.channel_b;
+    right_enable.output_to(pins.gpio1);
+
+    // === L298N setup ======================================================
+    let mut drive = differential_drive! {
+        left: (pins.gpio2, pins.gpio3, left_enable),
+        right: (pins.gpio4, pins.gpio5, right_enable),
+    }
+    .unwrap();
+
+    let mut timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    timer.delay_ms(1000);
+
+    // === Program Logic ====================================================
+    let maneuver = Maneuver::<10>::new()
+        .forward(u16::MAX, 2000)
+        .coast(2000)
+        .spin_right(55000, 2000)
+        .spin_left(55000, 2000)
+        .end_with(StopMode::Brake);
+
+    let mut scheduler = CommandScheduler::new(maneuver.sequence().unwrap());
+
+    // The timer counts microseconds
+    let now_ms = |timer: &hal::Timer| (timer.get_counter().ticks() / 1000) as u32;
+    scheduler.start(&now_ms(&timer));
+
+    while !scheduler.poll(&mut drive, &now_ms(&timer)).unwrap() {
+        timer.delay_ms(1);
+    }
+
+    // Loop forever
+    loop { }
+}
@@ -25,7 +25,7 @@ use cortex_m_rt::entry;
 use panic_halt as _;
 use stm32f4xx_hal::{pac, prelude::*};
 
-use l298_hbridge::{L298NHBridge, Command, Direction, StopMode};
+use l298_hbridge::{differential_drive, Maneuver, CommandScheduler, StopMode};
 
 #[entry]
 fn main() -> ! {
@@ -39,12 +39,6 @@ fn main() -> ! {
     let gpioa = dp.GPIOA.split(&mut rcc);
     let gpiob = dp.GPIOB.split(&mut rcc);
 
-    let left_dir1 = gpiob.pb3.into_push_pull_output();
-    let left_dir2 = gpiob.pb4.into_push_pull_output();
-
-    let right_dir1 = gpioa.pa8.into_push_pull_output();
-    let right_dir2 = gpiob.pb15.into_push_pull_output();
-
     // Setup PWM
     let (_, (_, tim1_ch2, ..)) = dp.TIM1.pwm_us(100.micros(), &mut rcc);
     let (_, (_, tim3_ch2, ..)) = dp.TIM3.pwm_us(100.micros(), &mut rcc);
@@ -56,37 +50,33 @@ fn main() -> ! {
     right_enable.enable();
 
     // === L298N setup ======================================================
-    let mut left_motor = L298NHBridge::new(left_dir1, left_dir2, left_enable).unwrap();
-    let mut right_motor = L298NHBridge::new(right_dir1, right_dir2, right_enable).unwrap();
+    let mut drive = differential_drive! {
+        left: (gpiob.pb3, gpiob.pb4, left_enable),
+        right: (gpioa.pa8, gpiob.pb15, right_enable),
+    }
+    .unwrap();
 
     let mut delay = cp.SYST.delay(&rcc.clocks);
     delay.delay_ms(1000);
 
     // === Program Logic ====================================================
-    left_motor.set(Command::Drive { direction: Direction::Forward, throttle: u16::MAX });
-    right_motor.set(Command::Drive { direction: Direction::Forward, throttle: u16::MAX });
-
-    delay.delay_ms(2000);
-
-    left_motor.set(Command::Stop(StopMode::Coast));
-    right_motor.set(Command::Stop(StopMode::Coast));
-
-    delay.delay_ms(2000);
-    
-    // Spin right
-    left_motor.set(Command::Drive { direction: Direction::Forward, throttle: 55000u16 });
-    right_motor.set(Command::Drive { direction: Direction::Reverse, throttle: 55000u16 });
-
-    delay.delay_ms(2000);
-
-    // Spin left
-    left_motor.set(Command::Drive { direction: Direction::Reverse, throttle: 55000u16 });
-    right_motor.set(Command::Drive { direction: Direction::Forward, throttle: 55000u16 });
-
-    delay.delay_ms(2000);
-
-    left_motor.set(Command::Stop(StopMode::Brake));
-    right_motor.set(Command::Stop(StopMode::Brake));
+    let maneuver = Maneuver::<10>::new()
+        .forward(u16::MAX, 2000)
+        .coast(2000)
+        .spin_right(55000, 2000)
+        .spin_left(55000, 2000)
+        .end_with(StopMode::Brake);
+
+    let mut scheduler = CommandScheduler::new(maneuver.sequence().unwrap());
+
+    // The delay doubles as a millisecond clock
+    let mut now_ms = 0u32;
+    scheduler.start(&now_ms);
+
+    while !scheduler.poll(&mut drive, &now_ms).unwrap() {
+        delay.delay_ms(1);
+        now_ms = now_ms.wrapping_add(1);
+    }
 
     // Loop forever
     loop { }
@@ -0,0 +1,94 @@
+//! Measures the cycle cost of `L298NHBridge::set` on the STM32F4 with the DWT cycle counter.
+//!
+//! The results are left in `Results` and the program stops at a breakpoint, so they can be
+//! read with a debugger (e.g. `print RESULTS` in gdb).
+//!
+//! # Wiring
+//! Uses the left motor wiring of the `basic` example:
+//! =============================
+//! |   L298N   |   STM32F4     |
+//! |-----------|---------------|
+//! |   En A    |   PB5         |
+//! |   I2      |   PB4         |
+//! |   I1      |   PB3         |
+//! =============================
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::DWT;
+use cortex_m_rt::entry;
+use panic_halt as _;
+use stm32f4xx_hal::{pac, prelude::*};
+
+use l298_hbridge::{L298NHBridge, Command, Direction, StopMode};
+
+/// Worst observed cycle counts for each kind of `set` call
+#[derive(Default)]
+struct Results {
+    /// Same direction, new throttle: the hot path
+    same_direction: u32,
+    /// Reversing under PWM
+    direction_change: u32,
+    brake: u32,
+    coast: u32,
+}
+
+static RESULTS: Mutex<RefCell<Option<Results>>> = Mutex::new(RefCell::new(None));
+
+const ITERATIONS: u16 = 1000;
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+
+    let mut rcc = dp.RCC.constrain();
+    let gpiob = dp.GPIOB.split(&mut rcc);
+
+    let dir1 = gpiob.pb3.into_push_pull_output();
+    let dir2 = gpiob.pb4.into_push_pull_output();
+
+    let (_, (_, tim3_ch2, ..)) = dp.TIM3.pwm_us(100.micros(), &mut rcc);
+    let mut enable = tim3_ch2.with(gpiob.pb5);
+    enable.enable();
+
+    let mut motor = L298NHBridge::new(dir1, dir2, enable).unwrap();
+
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
+    let mut measure = |cmd: Command| {
+        let start = DWT::cycle_count();
+        motor.set(cmd).unwrap();
+        DWT::cycle_count().wrapping_sub(start)
+    };
+
+    let mut results = Results::default();
+
+    for i in 0..ITERATIONS {
+        let throttle = i.wrapping_mul(65);
+
+        measure(Command::Drive { direction: Direction::Forward, throttle });
+        let cycles = measure(Command::Drive { direction: Direction::Forward, throttle: !throttle });
+        results.same_direction = results.same_direction.max(cycles);
+
+        let cycles = measure(Command::Drive { direction: Direction::Reverse, throttle });
+        results.direction_change = results.direction_change.max(cycles);
+
+        let cycles = measure(Command::Stop(StopMode::Brake));
+        results.brake = results.brake.max(cycles);
+
+        let cycles = measure(Command::Stop(StopMode::Coast));
+        results.coast = results.coast.max(cycles);
+    }
+
+    cortex_m::interrupt::free(|cs| RESULTS.borrow(cs).replace(Some(results)));
+    cortex_m::asm::bkpt();
+
+    loop { }
+}
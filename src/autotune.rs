@@ -0,0 +1,158 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::{IntoMillis, HBridgeDriver, EncoderReader, VelocityEstimator, Command, Direction, StopMode, PidGains, GAIN_FRAC_BITS, Error};
+
+/// Most oscillation cycles [`relay_autotune`] can measure
+const MAX_CYCLES: usize = 8;
+
+/// Settings for [`relay_autotune`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RelayTuneSettings {
+    /// Rate the velocity loop will run at, the estimator must be set up for it too
+    pub update_hz: u32,
+    /// Speed oscillated around, in encoder counts/s
+    pub setpoint: i32,
+    /// Throttle the relay switches around, roughly what holds the setpoint
+    pub bias: u16,
+    /// How far the relay steps the throttle above and below the bias
+    pub amplitude: u16,
+    /// Hysteresis of the relay in counts/s, enough to stop noise switching it but small
+    /// compared to the oscillation
+    pub hysteresis: u32,
+    /// Oscillation cycles to average over, at most 8. One more is run first and discarded.
+    pub cycles: u8,
+    /// Longest the experiment may run for, in milliseconds
+    pub timeout_ms: u32,
+}
+
+impl RelayTuneSettings {
+
+    pub fn new(
+        update_hz: u32,
+        setpoint: i32,
+        bias: u16,
+        amplitude: u16,
+        hysteresis: u32,
+        cycles: u8,
+        timeout: impl IntoMillis,
+    ) -> Self {
+        Self { update_hz, setpoint, bias, amplitude, hysteresis, cycles, timeout_ms: timeout.into_millis() }
+    }
+}
+
+
+/// Result of [`relay_autotune`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RelayTuneResult {
+    /// Ultimate gain in Q16.16 throttle per count/s, where the loop oscillates under P control
+    pub ultimate_gain: i32,
+    /// Ultimate period of the oscillation in milliseconds
+    pub period_ms: u32,
+    /// Half the peak to peak speed oscillation in counts/s
+    pub amplitude: u32,
+    /// Ziegler-Nichols PI gains for a loop run at `update_hz`, per update as [`Pid`] expects
+    ///
+    /// [`Pid`]: crate::Pid
+    pub gains: PidGains,
+}
+
+/// Tunes a velocity loop by relay feedback (Åström-Hägglund): the throttle is switched
+/// between `bias ± amplitude` each time the speed crosses the setpoint, making the loop
+/// oscillate at its ultimate period. The ultimate gain follows from the oscillation's
+/// amplitude, and PI gains from the Ziegler-Nichols rules.
+///
+/// `estimator` is updated at `settings.update_hz`, so should be the one the tuned loop will
+/// use, as its lag is part of the loop. Drives forward only. Returns `None` if the loop
+/// didn't oscillate steadily before the timeout. The motor is left coasting.
+pub fn relay_autotune<M, E, D>(
+    motor: &mut M,
+    encoder: &mut E,
+    estimator: &mut VelocityEstimator,
+    delay: &mut D,
+    settings: RelayTuneSettings,
+) -> Result<Option<RelayTuneResult>, Error>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+    D: DelayNs,
+{
+    let update_hz = settings.update_hz.max(1);
+    let period_us = 1_000_000 / update_hz;
+    let cycles = usize::from(settings.cycles).clamp(1, MAX_CYCLES);
+    let max_steps = u64::from(settings.timeout_ms) * u64::from(update_hz) / 1000;
+
+    let high = settings.bias.saturating_add(settings.amplitude);
+    let low = settings.bias.saturating_sub(settings.amplitude);
+    let upper = i64::from(settings.setpoint) + i64::from(settings.hysteresis);
+    let lower = i64::from(settings.setpoint) - i64::from(settings.hysteresis);
+
+    // Steps at which the relay switched high, which start each cycle, and the speed extremes
+    // within each cycle
+    let mut starts = [0u64; MAX_CYCLES + 2];
+    let mut extremes = [(i32::MAX, i32::MIN); MAX_CYCLES + 1];
+    let mut switches = 0;
+    let mut relay_high = true;
+
+    estimator.reset();
+    motor.set(Command::Drive { direction: Direction::Forward, throttle: high })?;
+
+    let mut step = 0;
+    while switches < cycles + 2 && step < max_steps {
+        delay.delay_us(period_us);
+        let speed = estimator.update_from(encoder);
+        step += 1;
+
+        if switches > 0 {
+            let (min, max) = &mut extremes[switches - 1];
+            *min = (*min).min(speed);
+            *max = (*max).max(speed);
+        }
+
+        if relay_high && i64::from(speed) > upper {
+            relay_high = false;
+            motor.set(Command::Drive { direction: Direction::Forward, throttle: low })?;
+        } else if !relay_high && i64::from(speed) < lower {
+            relay_high = true;
+            motor.set(Command::Drive { direction: Direction::Forward, throttle: high })?;
+            starts[switches] = step;
+            switches += 1;
+        }
+    }
+
+    motor.set(Command::Stop(StopMode::Coast))?;
+
+    if switches < cycles + 2 {
+        return Ok(None);
+    }
+
+    // The first cycle is still settling from the start, so is skipped
+    let period_steps = (starts[cycles + 1] - starts[1]) / cycles as u64;
+    let amplitude = extremes[1..=cycles].iter()
+        .map(|(min, max)| (i64::from(*max) - i64::from(*min)) as u64 / 2)
+        .sum::<u64>() / cycles as u64;
+
+    // Describing function of a relay with hysteresis: Ku = 4d / (pi * sqrt(a² - h²)), with
+    // pi ≈ 355/113
+    let hysteresis = u64::from(settings.hysteresis);
+    let effective = (amplitude * amplitude).saturating_sub(hysteresis * hysteresis).isqrt();
+    if effective == 0 || period_steps == 0 {
+        return Ok(None);
+    }
+
+    let amplitude_d = u64::from(high - low) / 2;
+    let ultimate_gain = ((4 * amplitude_d) << GAIN_FRAC_BITS) * 113 / (355 * effective);
+    let period_ms = period_steps * 1000 / u64::from(update_hz);
+
+    // Ziegler-Nichols PI: kp = 0.45 Ku, Ti = Tu / 1.2, so ki per update = kp * 1.2 / Tu
+    let kp = ultimate_gain * 45 / 100;
+    let ki = kp * 12 / (10 * period_steps);
+
+    let clamp = |gain: u64| gain.min(i32::MAX as u64) as i32;
+
+    Ok(Some(RelayTuneResult {
+        ultimate_gain: clamp(ultimate_gain),
+        period_ms: period_ms.min(u64::from(u32::MAX)) as u32,
+        amplitude: amplitude.min(u64::from(u32::MAX)) as u32,
+        gains: PidGains { kp: clamp(kp), ki: clamp(ki), kd: 0 },
+    }))
+}
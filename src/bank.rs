@@ -0,0 +1,268 @@
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, StopMode, Error};
+
+/// A fixed size bank of `N` motor drivers controlled through a single handle.
+///
+/// The drivers may be heterogeneous by storing trait objects, e.g.
+/// `MotorBank<&mut dyn HBridgeDriver, 4>`.
+///
+/// Starts can be staggered with [`MotorBank::start_all`], so the motors don't all pull
+/// their stall current from the battery in the same millisecond.
+///
+/// Commands to several motors are sent to every motor even if one fails, e.g. with a fault
+/// rejecting drive commands, and the first error is returned.
+pub struct MotorBank<D, const N: usize>
+where
+    D: HBridgeDriver,
+{
+    motors: [D; N],
+    stagger_ms: u32,
+    /// Drive commands held back by a staggered start, with their delay
+    pending: [Option<(Command, u32)>; N],
+    /// When the staggered start began
+    start_ms: u32,
+}
+
+impl<D, const N: usize> MotorBank<D, N>
+where
+    D: HBridgeDriver,
+{
+
+    pub fn new(motors: [D; N]) -> Self {
+        Self { motors, stagger_ms: 0, pending: [None; N], start_ms: 0 }
+    }
+
+    /// Sets the delay between the motors started by [`MotorBank::start_all`], zero (the
+    /// default) starts them together
+    pub fn set_stagger(&mut self, stagger: impl IntoMillis) {
+        self.stagger_ms = stagger.into_millis();
+    }
+
+    /// Sends `cmd` to the motor at `index`, cancelling any staggered start of it.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        self.pending[index] = None;
+        self.motors[index].set(cmd)
+    }
+
+    /// Sends `cmd` to the motor at `index`, returning `None` instead of panicking if `index`
+    /// is out of bounds.
+    pub fn checked_set(&mut self, index: usize, cmd: Command) -> Option<Result<(), Error>> {
+        (index < N).then(|| self.set(index, cmd))
+    }
+
+    /// Sends `cmds[i]` to the motor at index `i`, in index order.
+    pub fn set_all(&mut self, cmds: &[Command; N]) -> Result<(), Error> {
+        self.pending = [None; N];
+
+        let mut result = Ok(());
+        for (motor, cmd) in self.motors.iter_mut().zip(cmds) {
+            result = result.and(motor.set(*cmd));
+        }
+
+        result
+    }
+
+    /// Like [`MotorBank::set_all`], but drive commands to motors at a standstill are
+    /// staggered by the stagger delay each, in index order. Stops and commands to motors
+    /// already running are applied at once. [`MotorBank::poll`] applies the rest.
+    pub fn start_all(&mut self, cmds: &[Command; N], clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.pending = [None; N];
+        self.start_ms = now_ms;
+
+        let mut result = Ok(());
+        let mut delay_ms = 0u32;
+        for (i, cmd) in cmds.iter().enumerate() {
+            let starting = matches!(cmd, Command::Drive { throttle, .. } if *throttle > 0)
+                && self.motors[i].get_throttle() == 0;
+
+            if starting && delay_ms > 0 {
+                self.pending[i] = Some((*cmd, delay_ms));
+            } else {
+                result = result.and(self.motors[i].set(*cmd));
+            }
+
+            if starting {
+                delay_ms = delay_ms.saturating_add(self.stagger_ms);
+            }
+        }
+
+        result
+    }
+
+    /// Applies the staggered starts that are due. Must be called regularly after
+    /// [`MotorBank::start_all`], returns whether any are still waiting.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<bool, Error> {
+        let now_ms = clock.now_ms();
+        let elapsed = now_ms.wrapping_sub(self.start_ms);
+
+        let mut result = Ok(());
+        for (motor, pending) in self.motors.iter_mut().zip(self.pending.iter_mut()) {
+            if let Some((cmd, delay_ms)) = *pending
+                && elapsed >= delay_ms
+            {
+                *pending = None;
+                result = result.and(motor.set(cmd));
+            }
+        }
+
+        result.map(|()| self.pending.iter().any(Option::is_some))
+    }
+
+    /// Stops every motor in the bank using `stop_mode`, cancelling any staggered starts.
+    pub fn stop_all(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.pending = [None; N];
+
+        let mut result = Ok(());
+        for motor in self.motors.iter_mut() {
+            result = result.and(motor.set(Command::Stop(stop_mode)));
+        }
+
+        result
+    }
+
+    /// Brakes every motor in the bank at once, one command each, cancelling anything
+    /// queued. See [`MotorBank::stop_all`].
+    pub fn estop_all(&mut self) -> Result<(), Error> {
+        self.stop_all(StopMode::Brake)
+    }
+
+    /// Reinitializes every motor in the bank, see [`HBridgeDriver::reinit`], cancelling any
+    /// staggered starts.
+    pub fn reinit_all(&mut self) -> Result<(), Error> {
+        self.pending = [None; N];
+
+        let mut result = Ok(());
+        for motor in self.motors.iter_mut() {
+            result = result.and(motor.reinit());
+        }
+
+        result
+    }
+
+    pub fn get(&self, index: usize) -> Option<&D> {
+        self.motors.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut D> {
+        self.motors.get_mut(index)
+    }
+
+    /// Releases the underlying drivers.
+    pub fn free(self) -> [D; N] {
+        self.motors
+    }
+}
+
+/// A dynamically sized bank of motor drivers, for hosts that build their motor set at runtime
+/// (e.g. from configuration).
+///
+/// Like a [`MotorBank`], commands to several motors reach every motor even if one fails.
+#[cfg(feature = "alloc")]
+pub struct DynMotorBank {
+    motors: Vec<Box<dyn HBridgeDriver>>,
+}
+
+#[cfg(feature = "alloc")]
+impl DynMotorBank {
+
+    pub fn new() -> Self {
+        Self { motors: Vec::new() }
+    }
+
+    /// Appends `motor` to the bank, returning its index.
+    pub fn push(&mut self, motor: Box<dyn HBridgeDriver>) -> usize {
+        self.motors.push(motor);
+        self.motors.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.motors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.motors.is_empty()
+    }
+
+    /// Sends `cmd` to the motor at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        self.motors[index].set(cmd)
+    }
+
+    /// Sends `cmd` to the motor at `index`, returning `None` instead of panicking if `index`
+    /// is out of bounds.
+    pub fn checked_set(&mut self, index: usize, cmd: Command) -> Option<Result<(), Error>> {
+        self.motors.get_mut(index).map(|motor| motor.set(cmd))
+    }
+
+    /// Sends `cmds[i]` to the motor at index `i`, in index order. Commands beyond the
+    /// number of motors are ignored, as are motors beyond the number of commands.
+    pub fn set_all(&mut self, cmds: &[Command]) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (motor, cmd) in self.motors.iter_mut().zip(cmds) {
+            result = result.and(motor.set(*cmd));
+        }
+
+        result
+    }
+
+    /// Stops every motor in the bank using `stop_mode`.
+    pub fn stop_all(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        let mut result = Ok(());
+        for motor in self.motors.iter_mut() {
+            result = result.and(motor.set(Command::Stop(stop_mode)));
+        }
+
+        result
+    }
+
+    /// Brakes every motor in the bank at once, one command each. See [`DynMotorBank::stop_all`].
+    pub fn estop_all(&mut self) -> Result<(), Error> {
+        self.stop_all(StopMode::Brake)
+    }
+
+    /// Reinitializes every motor in the bank, see [`HBridgeDriver::reinit`].
+    pub fn reinit_all(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for motor in self.motors.iter_mut() {
+            result = result.and(motor.reinit());
+        }
+
+        result
+    }
+
+    pub fn get(&self, index: usize) -> Option<&dyn HBridgeDriver> {
+        self.motors.get(index).map(|motor| motor.as_ref())
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut (dyn HBridgeDriver + 'static)> {
+        self.motors.get_mut(index).map(|motor| motor.as_mut())
+    }
+
+    /// Releases the underlying drivers.
+    pub fn free(self) -> Vec<Box<dyn HBridgeDriver>> {
+        self.motors
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for DynMotorBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Vec<Box<dyn HBridgeDriver>>> for DynMotorBank {
+    fn from(motors: Vec<Box<dyn HBridgeDriver>>) -> Self {
+        Self { motors }
+    }
+}
@@ -0,0 +1,90 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::{IntoMillis, HBridgeDriver, Command, Direction, StopMode, Error};
+
+/// Result of [`calibrate`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Calibration {
+    /// Smallest throttle that moved the motor forward, `None` if it never moved
+    pub min_forward: Option<u16>,
+    /// Smallest throttle that moved the motor in reverse, `None` if it never moved
+    pub min_reverse: Option<u16>,
+}
+
+impl Calibration {
+
+    /// How much more throttle reverse needs to start moving than forward. Negative if
+    /// forward needs more. `None` unless both directions moved.
+    pub fn asymmetry(&self) -> Option<i32> {
+        match (self.min_forward, self.min_reverse) {
+            (Some(forward), Some(reverse)) => Some(i32::from(reverse) - i32::from(forward)),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the minimum throttle that moves `motor` in each direction.
+///
+/// The throttle is swept upward from zero in increments of `step`. After each increment the
+/// motor is given `settle` to respond before `is_moving` is asked whether it moved in the
+/// given direction. `is_moving` can check an encoder, or wait on a user confirmation (e.g. a
+/// button press).
+///
+/// The motor is coasted for `settle` between directions and left coasting afterwards.
+pub fn calibrate<M, D, F>(
+    motor: &mut M,
+    delay: &mut D,
+    step: u16,
+    settle: impl IntoMillis,
+    mut is_moving: F,
+) -> Result<Calibration, Error>
+where
+    M: HBridgeDriver,
+    D: DelayNs,
+    F: FnMut(Direction) -> bool,
+{
+    let settle_ms = settle.into_millis();
+    let min_forward = sweep(motor, delay, Direction::Forward, step, settle_ms, &mut is_moving)?;
+    let min_reverse = sweep(motor, delay, Direction::Reverse, step, settle_ms, &mut is_moving)?;
+
+    Ok(Calibration { min_forward, min_reverse })
+}
+
+/// Sweeps the throttle upward in `direction` until the motor moves
+fn sweep<M, D, F>(
+    motor: &mut M,
+    delay: &mut D,
+    direction: Direction,
+    step: u16,
+    settle_ms: u32,
+    is_moving: &mut F,
+) -> Result<Option<u16>, Error>
+where
+    M: HBridgeDriver,
+    D: DelayNs,
+    F: FnMut(Direction) -> bool,
+{
+    let step = step.max(1);
+    let mut throttle = 0u16;
+    let mut found = None;
+
+    loop {
+        motor.set(Command::Drive { direction, throttle })?;
+        delay.delay_ms(settle_ms);
+
+        if is_moving(direction) {
+            found = Some(throttle);
+            break;
+        }
+
+        if throttle == u16::MAX {
+            break;
+        }
+        throttle = throttle.saturating_add(step);
+    }
+
+    motor.set(Command::Stop(StopMode::Coast))?;
+    delay.delay_ms(settle_ms);
+
+    Ok(found)
+}
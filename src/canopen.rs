@@ -0,0 +1,279 @@
+use crate::{HBridgeDriver, EncoderReader, VelocityController, StopMode, Error};
+
+/// Object dictionary indices supported by [`Cia402Node`]
+pub mod object {
+    /// Controlword, u16, read/write
+    pub const CONTROLWORD: u16 = 0x6040;
+    /// Statusword, u16, read only
+    pub const STATUSWORD: u16 = 0x6041;
+    /// Modes of operation, i8, read/write, only profile velocity is supported
+    pub const MODES_OF_OPERATION: u16 = 0x6060;
+    /// Modes of operation display, i8, read only
+    pub const MODES_OF_OPERATION_DISPLAY: u16 = 0x6061;
+    /// Velocity actual value, i32 counts/s, read only
+    pub const VELOCITY_ACTUAL_VALUE: u16 = 0x606C;
+    /// Velocity window for target reached, u16 counts/s, read/write
+    pub const VELOCITY_WINDOW: u16 = 0x606D;
+    /// Target velocity, i32 counts/s, read/write
+    pub const TARGET_VELOCITY: u16 = 0x60FF;
+}
+
+/// The profile velocity mode of operation
+pub const PROFILE_VELOCITY_MODE: i8 = 3;
+
+/// Power drive system states of the CiA 402 state machine
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cia402State {
+    SwitchOnDisabled,
+    ReadyToSwitchOn,
+    SwitchedOn,
+    OperationEnabled,
+    QuickStopActive,
+    Fault,
+}
+
+/// Device control commands decoded from the controlword
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DeviceCommand {
+    DisableVoltage,
+    QuickStop,
+    Shutdown,
+    SwitchOn,
+    EnableOperation,
+}
+
+impl DeviceCommand {
+
+    fn decode(controlword: u16) -> Self {
+        let switch_on = controlword & 0x1 != 0;
+        let enable_voltage = controlword & 0x2 != 0;
+        let quick_stop = controlword & 0x4 != 0;
+        let enable_operation = controlword & 0x8 != 0;
+
+        match (enable_voltage, quick_stop, switch_on, enable_operation) {
+            (false, ..) => Self::DisableVoltage,
+            (true, false, ..) => Self::QuickStop,
+            (true, true, false, _) => Self::Shutdown,
+            (true, true, true, false) => Self::SwitchOn,
+            (true, true, true, true) => Self::EnableOperation,
+        }
+    }
+}
+
+const CONTROLWORD_FAULT_RESET: u16 = 1 << 7;
+const CONTROLWORD_HALT: u16 = 1 << 8;
+
+const STATUSWORD_VOLTAGE_ENABLED: u16 = 1 << 4;
+const STATUSWORD_REMOTE: u16 = 1 << 9;
+const STATUSWORD_TARGET_REACHED: u16 = 1 << 10;
+
+/// A minimal CANopen node for a [`VelocityController`], inspired by the CiA 402 drive
+/// profile in profile velocity mode.
+///
+/// Implements the power drive system state machine driven by the controlword and reported by
+/// the statusword, target velocity and velocity actual value. Velocities are in the
+/// controller's encoder counts/s. Any fault latched by the motor driver moves the node to
+/// [`Cia402State::Fault`], which is left with a fault reset once the fault has been cleared.
+///
+/// The node is transport agnostic: map the objects in [`object`] onto SDOs and PDOs with
+/// [`Cia402Node::read_object`] and [`Cia402Node::write_object`], and call
+/// [`Cia402Node::update`] at the controller's update rate.
+pub struct Cia402Node<M, E, const N: usize>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+    controller: VelocityController<M, E, N>,
+    state: Cia402State,
+    controlword: u16,
+    target_velocity: i32,
+    velocity_window: u16,
+}
+
+impl<M, E, const N: usize> Cia402Node<M, E, N>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+
+    /// Wraps `controller`, starting in [`Cia402State::SwitchOnDisabled`]
+    pub fn new(controller: VelocityController<M, E, N>) -> Self {
+        Self {
+            controller,
+            state: Cia402State::SwitchOnDisabled,
+            controlword: 0,
+            target_velocity: 0,
+            velocity_window: 0,
+        }
+    }
+
+    pub fn state(&self) -> Cia402State {
+        self.state
+    }
+
+    /// Writes the controlword, applying any state transition it commands
+    pub fn set_controlword(&mut self, controlword: u16) -> Result<(), Error> {
+        let fault_reset = controlword & CONTROLWORD_FAULT_RESET != 0
+            && self.controlword & CONTROLWORD_FAULT_RESET == 0;
+        self.controlword = controlword;
+
+        let next = match (self.state, DeviceCommand::decode(controlword)) {
+            (Cia402State::Fault, _) if fault_reset && self.controller.latched_faults().is_empty() => {
+                Cia402State::SwitchOnDisabled
+            },
+            (Cia402State::Fault, _) => Cia402State::Fault,
+            (_, DeviceCommand::DisableVoltage) => Cia402State::SwitchOnDisabled,
+            (Cia402State::OperationEnabled, DeviceCommand::QuickStop) => Cia402State::QuickStopActive,
+            (Cia402State::QuickStopActive, _) => Cia402State::QuickStopActive,
+            (_, DeviceCommand::QuickStop) => Cia402State::SwitchOnDisabled,
+            (Cia402State::SwitchOnDisabled, DeviceCommand::Shutdown) => Cia402State::ReadyToSwitchOn,
+            (Cia402State::SwitchOnDisabled, _) => Cia402State::SwitchOnDisabled,
+            (_, DeviceCommand::Shutdown) => Cia402State::ReadyToSwitchOn,
+            (_, DeviceCommand::SwitchOn) => Cia402State::SwitchedOn,
+            (_, DeviceCommand::EnableOperation) => Cia402State::OperationEnabled,
+        };
+
+        self.transition(next)
+    }
+
+    pub fn controlword(&self) -> u16 {
+        self.controlword
+    }
+
+    /// The statusword for the current state
+    pub fn statusword(&self) -> u16 {
+        let state_bits = match self.state {
+            Cia402State::SwitchOnDisabled => 0x0040,
+            Cia402State::ReadyToSwitchOn => 0x0021 | STATUSWORD_VOLTAGE_ENABLED,
+            Cia402State::SwitchedOn => 0x0023 | STATUSWORD_VOLTAGE_ENABLED,
+            Cia402State::OperationEnabled => 0x0027 | STATUSWORD_VOLTAGE_ENABLED,
+            Cia402State::QuickStopActive => 0x0007 | STATUSWORD_VOLTAGE_ENABLED,
+            Cia402State::Fault => 0x0008,
+        };
+
+        let error = i64::from(self.controller.speed()) - i64::from(self.effective_target());
+        let target_reached = if error.unsigned_abs() <= u64::from(self.velocity_window) {
+            STATUSWORD_TARGET_REACHED
+        } else {
+            0
+        };
+
+        state_bits | STATUSWORD_REMOTE | target_reached
+    }
+
+    /// Sets the target velocity in encoder counts/s, applied while operation is enabled
+    pub fn set_target_velocity(&mut self, counts_per_sec: i32) {
+        self.target_velocity = counts_per_sec;
+    }
+
+    pub fn target_velocity(&self) -> i32 {
+        self.target_velocity
+    }
+
+    /// The latest speed estimate in encoder counts/s
+    pub fn velocity_actual_value(&self) -> i32 {
+        self.controller.speed()
+    }
+
+    /// The statusword reports target reached while the speed is within `window` counts/s of
+    /// the target
+    pub fn set_velocity_window(&mut self, window: u16) {
+        self.velocity_window = window;
+    }
+
+    /// Reads an object from [`object`] as its raw bits, `None` if it isn't supported
+    pub fn read_object(&self, index: u16, subindex: u8) -> Option<u32> {
+        if subindex != 0 {
+            return None;
+        }
+
+        match index {
+            object::CONTROLWORD => Some(u32::from(self.controlword)),
+            object::STATUSWORD => Some(u32::from(self.statusword())),
+            object::MODES_OF_OPERATION | object::MODES_OF_OPERATION_DISPLAY => {
+                Some(PROFILE_VELOCITY_MODE as u8 as u32)
+            },
+            object::VELOCITY_ACTUAL_VALUE => Some(self.velocity_actual_value() as u32),
+            object::VELOCITY_WINDOW => Some(u32::from(self.velocity_window)),
+            object::TARGET_VELOCITY => Some(self.target_velocity as u32),
+            _ => None,
+        }
+    }
+
+    /// Writes an object from [`object`] from its raw bits. Returns `Ok(false)` if the object
+    /// isn't supported, is read only, or the value is out of range.
+    pub fn write_object(&mut self, index: u16, subindex: u8, value: u32) -> Result<bool, Error> {
+        if subindex != 0 {
+            return Ok(false);
+        }
+
+        match index {
+            object::CONTROLWORD => match u16::try_from(value) {
+                Ok(controlword) => self.set_controlword(controlword)?,
+                Err(_) => return Ok(false),
+            },
+            object::MODES_OF_OPERATION => return Ok(value as u8 as i8 == PROFILE_VELOCITY_MODE),
+            object::VELOCITY_WINDOW => match u16::try_from(value) {
+                Ok(window) => self.velocity_window = window,
+                Err(_) => return Ok(false),
+            },
+            object::TARGET_VELOCITY => self.target_velocity = value as i32,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Checks for faults, applies the target velocity and updates the controller. Must be
+    /// called at the controller's update rate.
+    pub fn update(&mut self) -> Result<(), Error> {
+        if self.state != Cia402State::Fault && !self.controller.latched_faults().is_empty() {
+            self.transition(Cia402State::Fault)?;
+        }
+
+        match self.state {
+            Cia402State::OperationEnabled => self.controller.set_speed(self.effective_target()),
+            // Quick stop option code 2: brake to a standstill, then switch on disabled
+            Cia402State::QuickStopActive
+                if self.controller.speed().unsigned_abs() <= u32::from(self.velocity_window) =>
+            {
+                self.transition(Cia402State::SwitchOnDisabled)?;
+            },
+            _ => {},
+        }
+
+        self.controller.update()
+    }
+
+    pub fn controller(&mut self) -> &mut VelocityController<M, E, N> {
+        &mut self.controller
+    }
+
+    /// Releases the velocity controller.
+    pub fn free(self) -> VelocityController<M, E, N> {
+        self.controller
+    }
+
+    /// The velocity the drive should be regulating to, zero unless operation is enabled and
+    /// not halted
+    fn effective_target(&self) -> i32 {
+        if self.state == Cia402State::OperationEnabled && self.controlword & CONTROLWORD_HALT == 0 {
+            self.target_velocity
+        } else {
+            0
+        }
+    }
+
+    fn transition(&mut self, next: Cia402State) -> Result<(), Error> {
+        if next == self.state {
+            return Ok(());
+        }
+        self.state = next;
+
+        match next {
+            Cia402State::OperationEnabled => Ok(()),
+            Cia402State::QuickStopActive => self.controller.stop(StopMode::Brake),
+            _ => self.controller.stop(StopMode::Coast),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+/// A monotonic millisecond clock, read by the time based features (watchdogs, schedulers,
+/// ramps, ...) whenever they're commanded or polled.
+///
+/// Timestamps are a free running `u32` millisecond count, which may wrap, so are only ever
+/// compared by their wrapping difference. Implemented by closures returning such a count,
+/// e.g. `|| millis()` over a SysTick counter, by a `u32` timestamp already read this loop,
+/// and with the `fugit` feature by a [`FugitClock`] over a timer's instants.
+pub trait Clock {
+    fn now_ms(&self) -> u32;
+
+    /// Milliseconds since `since_ms`, a timestamp read earlier from this clock
+    fn elapsed_ms(&self, since_ms: u32) -> u32 {
+        self.now_ms().wrapping_sub(since_ms)
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> u32,
+{
+    fn now_ms(&self) -> u32 {
+        self()
+    }
+}
+
+/// A timestamp read once per loop and passed to several features, so they all see the same
+/// time
+impl Clock for u32 {
+    fn now_ms(&self) -> u32 {
+        *self
+    }
+}
+
+/// A length of time taken by the time based features, for their timeouts, ramp times and
+/// manoeuvre lengths.
+///
+/// Plain `u32`s are milliseconds. With the `fugit` feature, which is on by default,
+/// millisecond `fugit` durations can be passed as well, e.g. `500.millis()` or `2.secs()`, so
+/// the unit is explicit. Durations at other tick rates can be `convert`ed first.
+pub trait IntoMillis {
+    fn into_millis(self) -> u32;
+}
+
+impl IntoMillis for u32 {
+    fn into_millis(self) -> u32 {
+        self
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl IntoMillis for fugit::MillisDurationU32 {
+    fn into_millis(self) -> u32 {
+        self.ticks()
+    }
+}
+
+/// Saturates at `u32::MAX` milliseconds
+#[cfg(feature = "fugit")]
+impl IntoMillis for fugit::MillisDurationU64 {
+    fn into_millis(self) -> u32 {
+        self.ticks().min(u64::from(u32::MAX)) as u32
+    }
+}
+
+/// A [`Clock`] over a function returning 64-bit `fugit` instants at `HZ`, e.g. the `now()`
+/// of an RTIC monotonic or an embassy time driver.
+///
+/// The instants are 64-bit so the millisecond count wraps cleanly at `u32::MAX`, which a
+/// 32-bit tick count converted to milliseconds wouldn't.
+#[cfg(feature = "fugit")]
+pub struct FugitClock<F, const HZ: u32>
+where
+    F: Fn() -> fugit::TimerInstantU64<HZ>,
+{
+    now: F,
+}
+
+#[cfg(feature = "fugit")]
+impl<F, const HZ: u32> FugitClock<F, HZ>
+where
+    F: Fn() -> fugit::TimerInstantU64<HZ>,
+{
+
+    pub fn new(now: F) -> Self {
+        Self { now }
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<F, const HZ: u32> Clock for FugitClock<F, HZ>
+where
+    F: Fn() -> fugit::TimerInstantU64<HZ>,
+{
+    fn now_ms(&self) -> u32 {
+        // Truncating keeps the low bits, so the count wraps like any other timestamp
+        (self.now)().duration_since_epoch().to_millis() as u32
+    }
+}
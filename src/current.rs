@@ -0,0 +1,419 @@
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, Direction, StopMode, Fault, Pid, Telemetry, Error};
+use crate::pid::slew_toward;
+
+/// A source of motor current readings, e.g. an ADC across the L298N's sense resistor.
+pub trait CurrentSensor {
+    /// The magnitude of the motor current in milliamps
+    fn current_ma(&mut self) -> u32;
+}
+
+impl<T> CurrentSensor for &mut T
+where
+    T: CurrentSensor + ?Sized,
+{
+    fn current_ma(&mut self) -> u32 {
+        (**self).current_ma()
+    }
+}
+
+/// Filtering applied by a [`FilteredCurrentSensor`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CurrentFilter {
+    /// Readings are passed through unfiltered
+    None,
+    /// Average of the last `N` readings
+    MovingAverage,
+    /// Single-pole IIR low pass, `filtered += alpha * (raw - filtered)`, with `alpha` in Q16
+    /// fixed point (`u16::MAX` is close to 1.0, i.e. barely filtered)
+    Iir { alpha: u16 },
+}
+
+/// Filters the readings of a noisy `CurrentSensor`, such as an ADC across a sense resistor.
+///
+/// The filtered reading is what it reports as a `CurrentSensor` itself, so it can be
+/// dropped in front of anything consuming current readings. The latest raw and filtered
+/// readings are both kept for inspection.
+pub struct FilteredCurrentSensor<S, const N: usize>
+where
+    S: CurrentSensor,
+{
+    sensor: S,
+    filter: CurrentFilter,
+    window: [u32; N],
+    index: usize,
+    filled: usize,
+    sum: u64,
+    /// IIR state in Q16
+    iir: Option<u64>,
+    raw_ma: u32,
+    filtered_ma: u32,
+}
+
+impl<S, const N: usize> FilteredCurrentSensor<S, N>
+where
+    S: CurrentSensor,
+{
+
+    /// Wraps `sensor`. `N` is the window length of [`CurrentFilter::MovingAverage`].
+    pub fn new(sensor: S, filter: CurrentFilter) -> Self {
+        Self {
+            sensor,
+            filter,
+            window: [0; N],
+            index: 0,
+            filled: 0,
+            sum: 0,
+            iir: None,
+            raw_ma: 0,
+            filtered_ma: 0,
+        }
+    }
+
+    /// Changes the filter, discarding the filter history.
+    pub fn set_filter(&mut self, filter: CurrentFilter) {
+        self.filter = filter;
+        self.reset();
+    }
+
+    /// Discards the filter history.
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.filled = 0;
+        self.sum = 0;
+        self.iir = None;
+    }
+
+    /// Takes a reading from the underlying sensor and filters it, returning the filtered value.
+    pub fn sample(&mut self) -> u32 {
+        let raw_ma = self.sensor.current_ma();
+        self.raw_ma = raw_ma;
+
+        self.filtered_ma = match self.filter {
+            CurrentFilter::MovingAverage if N > 0 => {
+                if self.filled == N {
+                    self.sum -= u64::from(self.window[self.index]);
+                } else {
+                    self.filled += 1;
+                }
+                self.window[self.index] = raw_ma;
+                self.sum += u64::from(raw_ma);
+                self.index = (self.index + 1) % N;
+
+                (self.sum / self.filled as u64) as u32
+            },
+            CurrentFilter::Iir { alpha } => {
+                let raw = u64::from(raw_ma) << 16;
+                let state = match self.iir {
+                    Some(state) if raw >= state => state + (((raw - state) * u64::from(alpha)) >> 16),
+                    Some(state) => state - (((state - raw) * u64::from(alpha)) >> 16),
+                    None => raw,
+                };
+                self.iir = Some(state);
+
+                (state >> 16) as u32
+            },
+            CurrentFilter::MovingAverage | CurrentFilter::None => raw_ma,
+        };
+
+        self.filtered_ma
+    }
+
+    /// The latest unfiltered reading in milliamps
+    pub fn raw_ma(&self) -> u32 {
+        self.raw_ma
+    }
+
+    /// The latest filtered reading in milliamps
+    pub fn filtered_ma(&self) -> u32 {
+        self.filtered_ma
+    }
+
+    /// Releases the underlying sensor.
+    pub fn free(self) -> S {
+        self.sensor
+    }
+}
+
+impl<S, const N: usize> CurrentSensor for FilteredCurrentSensor<S, N>
+where
+    S: CurrentSensor,
+{
+    fn current_ma(&mut self) -> u32 {
+        self.sample()
+    }
+}
+
+/// Regulates the current through a motor, and so its torque, to a setpoint by adjusting the
+/// throttle with a PI(D) loop.
+///
+/// Step changes of the torque setpoint can optionally be ramped inside the loop, see
+/// [`TorqueController::set_setpoint_ramp`].
+///
+/// [`TorqueController::update`] must be called at the fixed rate the [`Pid`] gains were
+/// tuned for.
+pub struct TorqueController<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    motor: M,
+    sensor: S,
+    pid: Pid,
+    torque_ma: i32,
+    /// The torque setpoint after ramping, what the loop actually regulates to
+    setpoint_ma: i32,
+    ramp: Option<u32>,
+    measured_ma: u32,
+}
+
+impl<M, S> TorqueController<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+
+    /// Wraps `motor` and `sensor`. The output of `pid` is the throttle, so its output limits
+    /// are set to `0..=u16::MAX`.
+    pub fn new(motor: M, sensor: S, mut pid: Pid) -> Self {
+        pid.set_output_limits(0, i32::from(u16::MAX));
+
+        Self { motor, sensor, pid, torque_ma: 0, setpoint_ma: 0, ramp: None, measured_ma: 0 }
+    }
+
+    /// Sets the current setpoint in milliamps. The sign selects the direction, positive is
+    /// forward. Zero coasts the motor.
+    pub fn set_torque_ma(&mut self, torque_ma: i32) {
+        self.torque_ma = torque_ma;
+    }
+
+    pub fn get_torque_ma(&self) -> i32 {
+        self.torque_ma
+    }
+
+    /// Limits how fast the loop's setpoint follows the torque setpoint, in milliamps per
+    /// update. `None` applies the torque setpoint immediately.
+    pub fn set_setpoint_ramp(&mut self, max_step: Option<u32>) {
+        self.ramp = max_step;
+    }
+
+    /// Reads the current and updates the throttle.
+    pub fn update(&mut self) -> Result<(), Error> {
+        let setpoint_ma = match self.ramp {
+            Some(max_step) => slew_toward(self.setpoint_ma, self.torque_ma, max_step),
+            None => self.torque_ma,
+        };
+        if setpoint_ma.signum() != self.setpoint_ma.signum() {
+            self.pid.reset();
+        }
+        self.setpoint_ma = setpoint_ma;
+        self.measured_ma = self.sensor.current_ma();
+
+        let direction = match self.setpoint_ma.signum() {
+            1 => Direction::Forward,
+            -1 => Direction::Reverse,
+            _ => return self.motor.set(Command::Stop(StopMode::Coast)),
+        };
+
+        let measured = self.measured_ma.min(i32::MAX as u32) as i32;
+        let throttle = self.pid.update(self.setpoint_ma.saturating_abs(), measured) as u16;
+
+        self.motor.set(Command::Drive { direction, throttle })
+    }
+
+    /// This cycle's current setpoint, measured current, signed throttle and motor faults
+    pub fn telemetry(&self, timestamp_ms: u32) -> Telemetry {
+        let telemetry = Telemetry::from_driver(&self.motor, timestamp_ms);
+
+        Telemetry {
+            setpoint: self.setpoint_ma,
+            throttle: telemetry.throttle * self.setpoint_ma.signum(),
+            current_ma: self.measured_ma,
+            ..telemetry
+        }
+    }
+
+    pub fn pid(&mut self) -> &mut Pid {
+        &mut self.pid
+    }
+
+    /// Releases the motor and sensor.
+    pub fn free(self) -> (M, S) {
+        (self.motor, self.sensor)
+    }
+}
+
+/// Current a [`ContinuousCurrentLimit`] derates to, in percent of the rating, below it so
+/// the bucket drains while derated
+const DERATE_PERCENT: u64 = 75;
+
+/// Enforces a continuous current rating on a motor driver, e.g. of the motor or of the
+/// L298N channel (2 A), while still allowing short peaks above it such as when starting.
+///
+/// The current above the rating fills a leaky bucket, which drains at the rating, so it
+/// tracks how far the time-averaged current has exceeded it. The bucket holds enough for
+/// twice the rating over the overload time. Once full, the throttle is capped where the
+/// current would be at three quarters of the rating if it follows the throttle, e.g. to half
+/// for a current 1.5 times the rating, so the bucket drains, and [`Fault::OVER_CURRENT`] is
+/// reported until it has drained empty again.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct ContinuousCurrentLimit<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    motor: M,
+    sensor: S,
+    rating_ma: u32,
+    /// Bucket size in milliamp milliseconds
+    capacity: u64,
+    level: u64,
+    last_ms: Option<u32>,
+    /// Last command requested
+    command: Option<Command>,
+    /// Throttle cap while derating
+    cap: Option<u16>,
+    measured_ma: u32,
+    trip_count: u32,
+}
+
+impl<M, S> ContinuousCurrentLimit<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+
+    /// Wraps `motor` and `sensor`, limiting the average current to `rating_ma` and tolerating
+    /// twice that for `overload`. A noisy sensor is best wrapped in a [`FilteredCurrentSensor`].
+    pub fn new(motor: M, sensor: S, rating_ma: u32, overload: impl IntoMillis) -> Self {
+        Self {
+            motor,
+            sensor,
+            rating_ma,
+            capacity: u64::from(rating_ma) * u64::from(overload.into_millis()),
+            level: 0,
+            last_ms: None,
+            command: None,
+            cap: None,
+            measured_ma: 0,
+            trip_count: 0,
+        }
+    }
+
+    /// Changes the rating, keeping how far the current has already exceeded it
+    pub fn set_rating(&mut self, rating_ma: u32, overload: impl IntoMillis) {
+        self.rating_ma = rating_ma;
+        self.capacity = u64::from(rating_ma) * u64::from(overload.into_millis());
+        self.level = self.level.min(self.capacity);
+    }
+
+    /// Reads the current and accounts for it since the last call, derating or restoring the
+    /// throttle. Must be called regularly, at the rate the current is sampled at.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        let current_ma = self.sensor.current_ma();
+        self.measured_ma = current_ma;
+
+        let Some(last_ms) = self.last_ms.replace(now_ms) else {
+            return Ok(());
+        };
+
+        let elapsed = u64::from(now_ms.wrapping_sub(last_ms));
+        let rating_ma = self.rating_ma;
+
+        self.level = if current_ma > rating_ma {
+            (self.level + u64::from(current_ma - rating_ma) * elapsed).min(self.capacity)
+        } else {
+            self.level.saturating_sub(u64::from(rating_ma - current_ma) * elapsed)
+        };
+
+        if self.cap.is_none() && self.level >= self.capacity && current_ma > rating_ma {
+            let throttle = u64::from(self.motor.get_throttle());
+            let target_ma = u64::from(rating_ma) * DERATE_PERCENT / 100;
+            self.cap = Some((throttle * target_ma / u64::from(current_ma)) as u16);
+            self.trip_count = self.trip_count.saturating_add(1);
+            return self.apply();
+        }
+
+        if self.cap.is_some() && self.level == 0 && current_ma <= rating_ma {
+            self.cap = None;
+            return self.apply();
+        }
+
+        Ok(())
+    }
+
+    /// Whether the average current has exceeded the rating and the throttle is capped
+    pub fn is_derating(&self) -> bool {
+        self.cap.is_some()
+    }
+
+    /// [`Fault::OVER_CURRENT`] while derating
+    pub fn faults(&self) -> Fault {
+        if self.cap.is_some() { Fault::OVER_CURRENT } else { Fault::NONE }
+    }
+
+    /// The latest current reading in milliamps
+    pub fn current_ma(&self) -> u32 {
+        self.measured_ma
+    }
+
+    /// Number of times the rating has been exceeded for too long, saturating at `u32::MAX`
+    pub fn trip_count(&self) -> u32 {
+        self.trip_count
+    }
+
+    pub fn reset_trip_count(&mut self) {
+        self.trip_count = 0;
+    }
+
+    /// Releases the motor and sensor.
+    pub fn free(self) -> (M, S) {
+        (self.motor, self.sensor)
+    }
+
+    fn apply(&mut self) -> Result<(), Error> {
+        match (self.command, self.cap) {
+            (Some(Command::Drive { direction, throttle }), Some(cap)) => {
+                self.motor.set(Command::Drive { direction, throttle: throttle.min(cap) })
+            }
+            (Some(cmd), _) => self.motor.set(cmd),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+impl<M, S> HBridgeDriver for ContinuousCurrentLimit<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    /// Sends `cmd` to the motor, capping its throttle while derating
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        self.command = Some(cmd);
+        self.apply()
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Reinitializes the motor, forgetting the last command. The bucket is kept, as the
+    /// motor hasn't cooled any faster.
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.command = None;
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.faults() | self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
@@ -0,0 +1,186 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::{IntoMillis, HBridgeDriver, CurrentSensor, EncoderReader, Command, Direction, StopMode, Error};
+
+/// Settings for [`self_test_encoder`] and [`self_test_current`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SelfTestSettings {
+    /// Throttle of each test pulse, low enough to be safe if the wiring is wrong
+    pub throttle: u16,
+    /// Length of each test pulse in milliseconds
+    pub pulse_ms: u32,
+    /// Time the motor is coasted for after each pulse, in milliseconds
+    pub settle_ms: u32,
+    /// Encoder counts a pulse must move for the motor to count as responding
+    pub min_counts: u32,
+    /// Current a pulse must draw for the motor to count as connected, in milliamps
+    pub min_current_ma: u32,
+    /// Current above which [`self_test_current`] ends a pulse early and reports a short, in
+    /// milliamps
+    pub max_current_ma: u32,
+}
+
+impl SelfTestSettings {
+
+    pub fn new(
+        throttle: u16,
+        pulse: impl IntoMillis,
+        settle: impl IntoMillis,
+        min_counts: u32,
+        min_current_ma: u32,
+        max_current_ma: u32,
+    ) -> Self {
+        Self {
+            throttle,
+            pulse_ms: pulse.into_millis(),
+            settle_ms: settle.into_millis(),
+            min_counts,
+            min_current_ma,
+            max_current_ma,
+        }
+    }
+}
+
+
+/// How the motor responded to a test pulse in one direction
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WiringStatus {
+    /// Responded as expected
+    Ok,
+    /// Moved the opposite way, the motor leads or direction inputs are swapped
+    Reversed,
+    /// Didn't move or draw current, the motor or its supply is disconnected
+    NoResponse,
+    /// Drew more current than a motor should at the test throttle, the outputs or motor
+    /// leads are shorted
+    Short,
+}
+
+/// Result of a wiring self-test
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SelfTestReport {
+    pub forward: WiringStatus,
+    pub reverse: WiringStatus,
+}
+
+impl SelfTestReport {
+
+    pub fn passed(&self) -> bool {
+        self.forward == WiringStatus::Ok && self.reverse == WiringStatus::Ok
+    }
+
+    /// Whether both directions ran backwards, the usual sign of swapped motor leads (or
+    /// swapped In1/In2), or of an encoder counting the other way
+    pub fn is_reversed(&self) -> bool {
+        self.forward == WiringStatus::Reversed && self.reverse == WiringStatus::Reversed
+    }
+
+    pub fn is_shorted(&self) -> bool {
+        self.forward == WiringStatus::Short || self.reverse == WiringStatus::Short
+    }
+}
+
+/// Checks `motor` turns the expected way by pulsing it in each direction at a low throttle
+/// and watching `encoder`, which must count up when driving forward.
+///
+/// The motor is left coasting.
+pub fn self_test_encoder<M, E, D>(
+    motor: &mut M,
+    encoder: &mut E,
+    delay: &mut D,
+    settings: SelfTestSettings,
+) -> Result<SelfTestReport, Error>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+    D: DelayNs,
+{
+    let mut check = |direction: Direction| -> Result<WiringStatus, Error> {
+        let start = encoder.count();
+        pulse(motor, delay, direction, settings)?;
+        let moved = encoder.count() - start;
+
+        let expected = match direction {
+            Direction::Forward => moved,
+            Direction::Reverse => -moved,
+        };
+
+        Ok(if expected.unsigned_abs() < u64::from(settings.min_counts) {
+            WiringStatus::NoResponse
+        } else if expected > 0 {
+            WiringStatus::Ok
+        } else {
+            WiringStatus::Reversed
+        })
+    };
+
+    let forward = check(Direction::Forward)?;
+    let reverse = check(Direction::Reverse)?;
+
+    Ok(SelfTestReport { forward, reverse })
+}
+
+/// Checks `motor` is connected and not shorted by pulsing it in each direction at a low
+/// throttle, reading `sensor` every millisecond. A pulse drawing more than the ceiling is
+/// ended at once, otherwise the reading at its end must reach the floor.
+///
+/// Current alone can't tell the direction of rotation, so this never reports
+/// [`WiringStatus::Reversed`]. Suited to production test fixtures. The motor is left
+/// coasting.
+pub fn self_test_current<M, S, D>(
+    motor: &mut M,
+    sensor: &mut S,
+    delay: &mut D,
+    settings: SelfTestSettings,
+) -> Result<SelfTestReport, Error>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+    D: DelayNs,
+{
+    let mut check = |direction: Direction| -> Result<WiringStatus, Error> {
+        motor.set(Command::Drive { direction, throttle: settings.throttle })?;
+
+        let mut current_ma = 0;
+        let mut shorted = false;
+        for _ in 0..settings.pulse_ms.max(1) {
+            delay.delay_ms(1);
+            current_ma = sensor.current_ma();
+
+            if current_ma > settings.max_current_ma {
+                shorted = true;
+                break;
+            }
+        }
+
+        motor.set(Command::Stop(StopMode::Coast))?;
+        delay.delay_ms(settings.settle_ms);
+
+        Ok(if shorted {
+            WiringStatus::Short
+        } else if current_ma >= settings.min_current_ma {
+            WiringStatus::Ok
+        } else {
+            WiringStatus::NoResponse
+        })
+    };
+
+    let forward = check(Direction::Forward)?;
+    let reverse = check(Direction::Reverse)?;
+
+    Ok(SelfTestReport { forward, reverse })
+}
+
+/// Drives `direction` for the pulse length, then coasts for the settle time
+fn pulse<M, D>(motor: &mut M, delay: &mut D, direction: Direction, settings: SelfTestSettings) -> Result<(), Error>
+where
+    M: HBridgeDriver,
+    D: DelayNs,
+{
+    motor.set(Command::Drive { direction, throttle: settings.throttle })?;
+    delay.delay_ms(settings.pulse_ms);
+    motor.set(Command::Stop(StopMode::Coast))?;
+    delay.delay_ms(settings.settle_ms);
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! `core::fmt::Display`, as short stable strings for character LCDs and serial consoles.
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::{BridgeInputs, Cia402State, Command, Direction, Fault, SolenoidState, StopMode, throttle_percent};
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Forward => f.write_str("FWD"),
+            Direction::Reverse => f.write_str("REV"),
+        }
+    }
+}
+
+impl Display for StopMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StopMode::Brake => f.write_str("BRAKE"),
+            StopMode::Coast => f.write_str("COAST"),
+        }
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Drive { direction, throttle } => {
+                write!(f, "{} {}%", direction, throttle_percent(*throttle))
+            },
+            Command::Stop(stop_mode) => write!(f, "{}", stop_mode),
+        }
+    }
+}
+
+impl Display for BridgeInputs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeInputs::Forward => f.write_str("FWD"),
+            BridgeInputs::Reverse => f.write_str("REV"),
+            BridgeInputs::Brake => f.write_str("BRAKE"),
+            BridgeInputs::Off => f.write_str("OFF"),
+        }
+    }
+}
+
+/// `OK` without faults, otherwise e.g. `FAULT:OC` or `FAULT:OC,OT`
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("OK");
+        }
+
+        f.write_str("FAULT:")?;
+        for (i, code) in self.codes().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(code)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for SolenoidState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SolenoidState::Released => f.write_str("RELEASED"),
+            SolenoidState::PullIn { .. } => f.write_str("PULL-IN"),
+            SolenoidState::Holding => f.write_str("HOLDING"),
+        }
+    }
+}
+
+impl Display for Cia402State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Cia402State::SwitchOnDisabled => f.write_str("SW-ON-DIS"),
+            Cia402State::ReadyToSwitchOn => f.write_str("READY"),
+            Cia402State::SwitchedOn => f.write_str("SW-ON"),
+            Cia402State::OperationEnabled => f.write_str("OP-EN"),
+            Cia402State::QuickStopActive => f.write_str("QSTOP"),
+            Cia402State::Fault => f.write_str("FAULT"),
+        }
+    }
+}
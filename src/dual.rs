@@ -0,0 +1,235 @@
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+use crate::{HBridgeDriver, MotorGroup, Command, StopMode, Fault, Error};
+
+/// State shared by the two channels of an [`L298NDual`], which keeps the combined invariants
+/// once the channels have been split into different tasks or interrupt contexts.
+///
+/// Only atomic loads and stores are used, so it works on targets without compare-and-swap.
+pub struct DualCore {
+    estop: AtomicBool,
+    /// Drive throttle of each channel, at least what is applied
+    throttles: [AtomicU16; 2],
+    /// Most the two channels' throttles may add up to
+    budget: u32,
+}
+
+impl DualCore {
+
+    /// `budget` is the most the two drive throttles may add up to, e.g. to keep the package
+    /// within its combined dissipation. `2 * u16::MAX` doesn't limit them.
+    pub const fn new(budget: u32) -> Self {
+        Self {
+            estop: AtomicBool::new(false),
+            throttles: [AtomicU16::new(0), AtomicU16::new(0)],
+            budget,
+        }
+    }
+
+    /// Flags an emergency stop. Each channel brakes on its next command or poll.
+    pub fn estop(&self) {
+        self.estop.store(true, Ordering::SeqCst);
+    }
+
+    pub fn clear_estop(&self) {
+        self.estop.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_estopped(&self) -> bool {
+        self.estop.load(Ordering::SeqCst)
+    }
+
+    /// The combined drive throttle of both channels
+    pub fn total_throttle(&self) -> u32 {
+        self.throttles.iter().map(|throttle| u32::from(throttle.load(Ordering::SeqCst))).sum()
+    }
+}
+
+/// Both bridges of one **L298N**, as two drivers sharing a [`DualCore`].
+///
+/// The channels can be used together through this handle, or moved into different tasks or
+/// interrupt contexts with [`L298NDual::split`].
+pub struct L298NDual<'a, A, B>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+{
+    a: DualChannel<'a, A>,
+    b: DualChannel<'a, B>,
+}
+
+impl<'a, A, B> L298NDual<'a, A, B>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+{
+
+    pub fn new(a: A, b: B, core: &'a DualCore) -> Self {
+        Self {
+            a: DualChannel { motor: a, core, index: 0 },
+            b: DualChannel { motor: b, core, index: 1 },
+        }
+    }
+
+    /// Channel A, Out1/Out2
+    pub fn a(&mut self) -> &mut DualChannel<'a, A> {
+        &mut self.a
+    }
+
+    /// Channel B, Out3/Out4
+    pub fn b(&mut self) -> &mut DualChannel<'a, B> {
+        &mut self.b
+    }
+
+    /// Flags an emergency stop and brakes both channels
+    pub fn estop(&mut self) -> Result<(), Error> {
+        let a = self.a.estop();
+        a.and(self.b.stop(StopMode::Brake))
+    }
+
+    /// Stops both channels using `stop_mode`
+    pub fn stop_all(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        let a = self.a.stop(stop_mode);
+        a.and(self.b.stop(stop_mode))
+    }
+
+    /// Same as [`L298NDual::estop`], for a common stop surface across the aggregates
+    pub fn estop_all(&mut self) -> Result<(), Error> {
+        self.estop()
+    }
+
+    /// Splits into the two channels, which keep sharing the core
+    pub fn split(self) -> (DualChannel<'a, A>, DualChannel<'a, B>) {
+        (self.a, self.b)
+    }
+
+    /// Releases the underlying drivers.
+    pub fn free(self) -> (A, B) {
+        (self.a.free(), self.b.free())
+    }
+}
+
+/// Channel A at index 0, channel B at index 1
+impl<A, B> MotorGroup for L298NDual<'_, A, B>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+{
+    fn set_motor(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        match index {
+            0 => self.a.set(cmd),
+            1 => self.b.set(cmd),
+            _ => Err(Error::ConfigInvalid),
+        }
+    }
+}
+
+/// One channel of an [`L298NDual`].
+///
+/// Drive throttles are limited to what the other channel leaves of the core's budget, and
+/// drive commands brake instead while the core is emergency stopped.
+pub struct DualChannel<'a, M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    core: &'a DualCore,
+    index: usize,
+}
+
+impl<M> DualChannel<'_, M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Flags an emergency stop for both channels and brakes this one
+    pub fn estop(&mut self) -> Result<(), Error> {
+        self.core.estop();
+        self.stop(StopMode::Brake)
+    }
+
+    /// Brakes if the other channel flagged an emergency stop. Call regularly while driving,
+    /// if the channel may otherwise go a while without a command.
+    pub fn poll(&mut self) -> Result<(), Error> {
+        if self.core.is_estopped() && self.core.throttles[self.index].load(Ordering::SeqCst) != 0 {
+            return self.stop(StopMode::Brake);
+        }
+
+        Ok(())
+    }
+
+    pub fn core(&self) -> &DualCore {
+        self.core
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+
+    fn stop(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.motor.set(Command::Stop(stop_mode))?;
+        self.core.throttles[self.index].store(0, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+impl<M> HBridgeDriver for DualChannel<'_, M>
+where
+    M: HBridgeDriver,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        let (direction, throttle) = match cmd {
+            Command::Drive { direction, throttle } => (direction, throttle),
+            Command::Stop(stop_mode) => return self.stop(stop_mode),
+        };
+
+        if self.core.is_estopped() {
+            return self.stop(StopMode::Brake);
+        }
+
+        // Claims the higher of the old and new throttle before reading the other channel's,
+        // so whichever channel reads second sees at least what the first applies
+        let own = &self.core.throttles[self.index];
+        let previous = own.load(Ordering::SeqCst);
+        own.store(previous.max(throttle), Ordering::SeqCst);
+
+        let other = u32::from(self.core.throttles[1 - self.index].load(Ordering::SeqCst));
+        let allowed = self.core.budget.saturating_sub(other).min(u32::from(u16::MAX)) as u16;
+        let throttle = throttle.min(allowed);
+
+        // Hands back the claim if the motor kept its old throttle
+        if let Err(error) = self.motor.set(Command::Drive { direction, throttle }) {
+            own.store(previous, Ordering::SeqCst);
+            return Err(error);
+        }
+
+        own.store(throttle, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()?;
+        self.core.throttles[self.index].store(0, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
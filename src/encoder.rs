@@ -0,0 +1,164 @@
+/// A source of accumulated encoder counts, e.g. a timer in quadrature encoder mode.
+///
+/// Implementations are responsible for extending a wrapping hardware counter into the
+/// accumulated count.
+pub trait EncoderReader {
+    /// Signed count accumulated since the encoder was started, positive is forward
+    fn count(&mut self) -> i64;
+}
+
+impl<T> EncoderReader for &mut T
+where
+    T: EncoderReader + ?Sized,
+{
+    fn count(&mut self) -> i64 {
+        (**self).count()
+    }
+}
+
+/// Number of fractional bits in the fixed point filter state and gains
+const FRAC_BITS: u32 = 16;
+
+/// Alpha-beta filter estimating velocity from encoder counts, much smoother at low speeds
+/// than differencing raw counts.
+///
+/// The gains are in Q16 fixed point, i.e. `1 << 16` is 1.0. Lower gains filter more
+/// heavily at the cost of lag. A critically damped filter is a good starting point, with
+/// `beta ≈ alpha² / (2 - alpha)`.
+pub struct VelocityEstimator {
+    update_hz: u32,
+    alpha: i64,
+    beta: i64,
+    /// Estimated position in counts, Q16
+    position: Option<i64>,
+    /// Estimated velocity in counts per update, Q16
+    velocity: i64,
+}
+
+impl VelocityEstimator {
+
+    /// `update_hz` is the fixed rate [`VelocityEstimator::update`] will be called at.
+    pub fn new(update_hz: u32, alpha: u32, beta: u32) -> Self {
+        Self {
+            update_hz,
+            alpha: i64::from(alpha),
+            beta: i64::from(beta),
+            position: None,
+            velocity: 0,
+        }
+    }
+
+    pub fn set_gains(&mut self, alpha: u32, beta: u32) {
+        self.alpha = i64::from(alpha);
+        self.beta = i64::from(beta);
+    }
+
+    /// Feeds the latest encoder `count`, returning the new velocity estimate in counts/s.
+    pub fn update(&mut self, count: i64) -> i32 {
+        let measured = count << FRAC_BITS;
+
+        let Some(position) = self.position else {
+            self.position = Some(measured);
+            return 0;
+        };
+
+        let predicted = position + self.velocity;
+        let residual = measured - predicted;
+        self.position = Some(predicted + ((self.alpha * residual) >> FRAC_BITS));
+        self.velocity += (self.beta * residual) >> FRAC_BITS;
+
+        self.velocity()
+    }
+
+    /// Reads `encoder` and feeds the count, see [`VelocityEstimator::update`].
+    pub fn update_from<E: EncoderReader>(&mut self, encoder: &mut E) -> i32 {
+        self.update(encoder.count())
+    }
+
+    /// The current velocity estimate in counts/s
+    pub fn velocity(&self) -> i32 {
+        let velocity = (i128::from(self.velocity) * i128::from(self.update_hz)) >> FRAC_BITS;
+
+        velocity.clamp(i128::from(i32::MIN), i128::from(i32::MAX)) as i32
+    }
+
+    /// The current position estimate in counts
+    pub fn position(&self) -> i64 {
+        self.position.unwrap_or(0) >> FRAC_BITS
+    }
+
+    /// Forgets the filter state, e.g. after the encoder count was reset.
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.velocity = 0;
+    }
+}
+
+/// Converts between encoder counts/s and the speed of the output shaft, so setpoints can be
+/// given in units that carry over between robots.
+///
+/// The encoder is assumed to be on the motor shaft, ahead of a `gear_num:gear_den` reduction
+/// (e.g. `30:1`, or `97:10` for 9.7:1). For an encoder on the output shaft use `1:1`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpeedScale {
+    counts_per_rev: u32,
+    gear_num: u32,
+    gear_den: u32,
+}
+
+/// 2π as a fraction, accurate to within 0.00001%
+const TWO_PI_NUM: i64 = 710;
+const TWO_PI_DEN: i64 = 113;
+
+impl SpeedScale {
+
+    /// `counts_per_rev` is the number of encoder counts per motor shaft revolution, after
+    /// any quadrature multiplication.
+    pub fn new(counts_per_rev: u32, gear_num: u32, gear_den: u32) -> Self {
+        Self {
+            counts_per_rev: counts_per_rev.max(1),
+            gear_num: gear_num.max(1),
+            gear_den: gear_den.max(1),
+        }
+    }
+
+    /// Encoder counts per output shaft revolution, as a fraction
+    fn counts_per_output_rev(&self) -> (i64, i64) {
+        (i64::from(self.counts_per_rev) * i64::from(self.gear_num), i64::from(self.gear_den))
+    }
+
+    /// Converts encoder counts/s to output shaft RPM.
+    pub fn to_rpm(&self, counts_per_sec: i32) -> i32 {
+        let (num, den) = self.counts_per_output_rev();
+        saturate(i64::from(counts_per_sec) * 60 * den / num)
+    }
+
+    /// Converts output shaft RPM to encoder counts/s.
+    pub fn from_rpm(&self, rpm: i32) -> i32 {
+        let (num, den) = self.counts_per_output_rev();
+        saturate(i64::from(rpm) * num / (60 * den))
+    }
+
+    /// Converts encoder counts/s to output shaft speed in milliradians/s.
+    pub fn to_mrad_per_sec(&self, counts_per_sec: i32) -> i32 {
+        let (num, den) = self.counts_per_output_rev();
+        saturate(i64::from(counts_per_sec) * 1000 * TWO_PI_NUM * den / (TWO_PI_DEN * num))
+    }
+
+    /// Converts output shaft speed in milliradians/s to encoder counts/s.
+    pub fn from_mrad_per_sec(&self, mrad_per_sec: i32) -> i32 {
+        let (num, den) = self.counts_per_output_rev();
+        saturate(i64::from(mrad_per_sec) * TWO_PI_DEN * num / (1000 * TWO_PI_NUM * den))
+    }
+}
+
+impl Default for SpeedScale {
+    /// One count per revolution, without gearing
+    fn default() -> Self {
+        Self::new(1, 1, 1)
+    }
+}
+
+fn saturate(value: i64) -> i32 {
+    value.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
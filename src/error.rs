@@ -0,0 +1,69 @@
+use core::convert::Infallible;
+use core::fmt::{self, Display, Formatter};
+
+use embedded_hal::{digital, pwm};
+
+use crate::{BridgeOutput, Fault};
+
+/// One error type for every backend and subsystem of the crate, so an application can match
+/// on it regardless of where a failure came from.
+///
+/// Every driver and subsystem returns it. Backend errors are wrapped by their embedded-hal
+/// kind with [`Error::pin`] and [`Error::pwm`], and infallible backends convert into it, so
+/// results can be propagated with `?` throughout.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Error {
+    /// A direction or other digital output failed
+    Pin(digital::ErrorKind),
+    /// A PWM output failed
+    Pwm(pwm::ErrorKind),
+    /// An output read back in a different state than it was set to
+    VerifyFailed(BridgeOutput),
+    /// The operation is already in progress or the resource is in use
+    Busy,
+    /// The driver hasn't been initialized or armed, so won't drive
+    Disarmed,
+    /// Drive commands are rejected until these faults are cleared
+    Faulted(Fault),
+    /// The backend or configuration doesn't support the operation
+    Unsupported,
+    /// A setting or motor index is out of its valid range
+    ConfigInvalid,
+}
+
+impl Error {
+
+    /// Wraps the error of a digital output
+    pub fn pin<E: digital::Error>(error: E) -> Self {
+        Error::Pin(error.kind())
+    }
+
+    /// Wraps the error of a PWM output
+    pub fn pwm<E: pwm::Error>(error: E) -> Self {
+        Error::Pwm(error.kind())
+    }
+}
+
+impl From<Infallible> for Error {
+    fn from(error: Infallible) -> Self {
+        match error {}
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pin(_) => f.write_str("PIN"),
+            Error::Pwm(_) => f.write_str("PWM"),
+            Error::VerifyFailed(BridgeOutput::Dir1) => f.write_str("VERIFY:IN1"),
+            Error::VerifyFailed(BridgeOutput::Dir2) => f.write_str("VERIFY:IN2"),
+            Error::Busy => f.write_str("BUSY"),
+            Error::Disarmed => f.write_str("DISARMED"),
+            Error::Faulted(faults) => write!(f, "{}", faults),
+            Error::Unsupported => f.write_str("UNSUPPORTED"),
+            Error::ConfigInvalid => f.write_str("CONFIG"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
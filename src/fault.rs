@@ -0,0 +1,248 @@
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use crate::{HBridgeDriver, Command, StopMode, Error};
+
+/// Number of distinct faults, one per bit of a [`Fault`]
+const FAULT_COUNT: usize = 7;
+
+/// A set of fault conditions, combined as bit flags
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Fault(u8);
+
+impl Fault {
+    pub const NONE: Self = Self(0);
+    pub const OVER_CURRENT: Self = Self(1 << 0);
+    pub const STALL: Self = Self(1 << 1);
+    pub const TIMEOUT: Self = Self(1 << 2);
+    pub const OVER_TEMPERATURE: Self = Self(1 << 3);
+    pub const UNDERVOLTAGE: Self = Self(1 << 4);
+    pub const VERIFY_MISMATCH: Self = Self(1 << 5);
+    pub const OVER_SPEED: Self = Self(1 << 6);
+    pub const ALL: Self = Self((1 << FAULT_COUNT) - 1);
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Builds a fault set from raw bits, dropping unknown bits
+    pub const fn from_bits_truncate(bits: u8) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every fault in `other` is also in `self`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether any fault in `other` is also in `self`
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Short codes of the faults in the set, e.g. `OC` for [`Fault::OVER_CURRENT`], lowest
+    /// bit first
+    pub fn codes(self) -> impl Iterator<Item = &'static str> {
+        FAULT_CODES.iter()
+            .enumerate()
+            .filter(move |(bit, _)| self.0 & (1 << bit) != 0)
+            .map(|(_, code)| *code)
+    }
+}
+
+/// Short code of each fault, indexed by bit
+const FAULT_CODES: [&str; FAULT_COUNT] = ["OC", "STALL", "TO", "OT", "UV", "VERIFY", "OS"];
+
+impl BitOr for Fault {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Fault {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Fault {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Fault {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Not for Fault {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0 & Self::ALL.0)
+    }
+}
+
+/// What a [`FaultMonitor`] does when a fault is reported
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultPolicy {
+    /// The fault is recorded, but the motor keeps running
+    Ignore,
+    /// The motor coasts, but later drive commands are accepted
+    Coast,
+    /// The motor brakes and drive commands are rejected until the fault is cleared
+    BrakeAndLatch,
+}
+
+/// Applies a [`FaultPolicy`] per [`Fault`] to a motor driver, consolidating the faults of
+/// all protection features under one surface.
+///
+/// Faults are reported with [`FaultMonitor::report`], typically straight from the `faults()`
+/// of the protection features (e.g. [`UnderVoltageLockout::faults`]). Reported faults are
+/// recorded until [`FaultMonitor::clear_faults`].
+///
+/// [`UnderVoltageLockout::faults`]: crate::UnderVoltageLockout::faults
+pub struct FaultMonitor<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    policies: [FaultPolicy; FAULT_COUNT],
+    faults: Fault,
+    latched: Fault,
+}
+
+impl<M> FaultMonitor<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Wraps `motor`, with every fault set to [`FaultPolicy::BrakeAndLatch`].
+    pub fn new(motor: M) -> Self {
+        Self {
+            motor,
+            policies: [FaultPolicy::BrakeAndLatch; FAULT_COUNT],
+            faults: Fault::NONE,
+            latched: Fault::NONE,
+        }
+    }
+
+    /// Sets the policy of every fault in `faults`.
+    pub fn set_policy(&mut self, faults: Fault, policy: FaultPolicy) {
+        for (bit, slot) in self.policies.iter_mut().enumerate() {
+            if faults.contains(Fault(1 << bit)) {
+                *slot = policy;
+            }
+        }
+    }
+
+    pub fn get_policy(&self, fault: Fault) -> Option<FaultPolicy> {
+        let bit = fault.bits().trailing_zeros() as usize;
+        self.policies.get(bit).copied()
+    }
+
+    /// Records `faults` and applies their policies. Reporting an already recorded fault
+    /// doesn't apply its policy again.
+    pub fn report(&mut self, faults: Fault) -> Result<(), Error> {
+        let new = faults & !self.faults;
+        self.faults |= new;
+
+        let mut stop = None;
+        for (bit, policy) in self.policies.iter().enumerate() {
+            if !new.contains(Fault(1 << bit)) {
+                continue;
+            }
+
+            match policy {
+                FaultPolicy::Ignore => {},
+                FaultPolicy::Coast => stop = stop.or(Some(StopMode::Coast)),
+                FaultPolicy::BrakeAndLatch => {
+                    self.latched |= Fault(1 << bit);
+                    stop = Some(StopMode::Brake);
+                },
+            }
+        }
+
+        match stop {
+            Some(stop_mode) => self.motor.set(Command::Stop(stop_mode)),
+            None => Ok(()),
+        }
+    }
+
+    /// Every fault reported since the last clear
+    pub fn faults(&self) -> Fault {
+        self.faults
+    }
+
+    /// The reported faults currently rejecting drive commands
+    pub fn latched(&self) -> Fault {
+        self.latched
+    }
+
+    /// Clears every recorded fault, accepting drive commands again.
+    pub fn clear_faults(&mut self) {
+        self.faults = Fault::NONE;
+        self.latched = Fault::NONE;
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M> HBridgeDriver for FaultMonitor<M>
+where
+    M: HBridgeDriver,
+{
+    /// Passes `cmd` to the motor, rejecting drive commands with [`Error::Faulted`] while a
+    /// fault is latched
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd {
+            Command::Drive { .. } if !self.latched.is_empty() => Err(Error::Faulted(self.latched)),
+            cmd => self.motor.set(cmd),
+        }
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Reinitializes the motor and clears every recorded fault. Faults whose cause persists
+    /// are latched again on their next report.
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()?;
+        self.clear_faults();
+
+        Ok(())
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.latched | self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
@@ -0,0 +1,51 @@
+/// Highest PWM frequency on the enable inputs recommended by the L298 datasheet
+pub const MAX_PWM_FREQUENCY_HZ: u32 = 40_000;
+
+/// Below this PWM frequency the motor windings whine audibly
+pub const AUDIBLE_PWM_FREQUENCY_HZ: u32 = 20_000;
+
+/// Reports the frequency of a PWM output, so drivers can validate it and expose it in
+/// telemetry.
+///
+/// HAL PWM types don't report their frequency in a common way, so implement this on a
+/// newtype around the PWM channel, or use [`L298NHBridge::set_pwm_frequency_hz`].
+///
+/// [`L298NHBridge::set_pwm_frequency_hz`]: crate::L298NHBridge::set_pwm_frequency_hz
+pub trait PwmFrequency {
+    fn pwm_frequency_hz(&self) -> u32;
+}
+
+impl<T> PwmFrequency for &mut T
+where
+    T: PwmFrequency + ?Sized,
+{
+    fn pwm_frequency_hz(&self) -> u32 {
+        (**self).pwm_frequency_hz()
+    }
+}
+
+/// A PWM frequency judged against the L298's recommended switching range
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PwmFrequencyCheck {
+    /// Within the recommended range and inaudible
+    Ok,
+    /// Usable, but the motor will whine
+    Audible,
+    /// Above [`MAX_PWM_FREQUENCY_HZ`], the L298's switching losses and slow outputs will
+    /// distort the duty cycle and heat the chip
+    AboveMaximum,
+    /// Zero, the PWM isn't running
+    Stopped,
+}
+
+impl PwmFrequencyCheck {
+
+    pub fn check(frequency_hz: u32) -> Self {
+        match frequency_hz {
+            0 => Self::Stopped,
+            hz if hz > MAX_PWM_FREQUENCY_HZ => Self::AboveMaximum,
+            hz if hz < AUDIBLE_PWM_FREQUENCY_HZ => Self::Audible,
+            _ => Self::Ok,
+        }
+    }
+}
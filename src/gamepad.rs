@@ -0,0 +1,170 @@
+use crate::{HBridgeDriver, DifferentialDrive, AnalogAxis, StopMode, Error};
+
+/// The fields of a gamepad HID report used for driving, as parsed by the USB host stack.
+///
+/// Sticks are unsigned with 128 at center and Y increasing downwards, as in the HID usage
+/// tables. Triggers read 0 when released.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GamepadReport {
+    pub left_x: u8,
+    pub left_y: u8,
+    pub right_x: u8,
+    pub right_y: u8,
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    /// One bit per button, button `n` is bit `n`
+    pub buttons: u16,
+}
+
+/// Which gamepad controls drive the robot
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DriveStyle {
+    /// Left stick Y for throttle, left stick X to turn
+    Arcade,
+    /// Left stick Y for throttle, right stick X to turn
+    SplitArcade,
+    /// Left and right stick Y for the left and right sides
+    Tank,
+    /// Right trigger forward, left trigger reverse, left stick X to turn
+    Triggers,
+}
+
+/// Mixer inputs decoded from a [`GamepadReport`], signed full scale
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadInput {
+    Arcade { throttle: i32, turn: i32 },
+    Tank { left: i32, right: i32 },
+    /// The emergency stop button has been pressed
+    EmergencyStop,
+}
+
+impl GamepadInput {
+
+    /// Applies the input to `drive`, braking on an emergency stop
+    pub fn apply<L, R>(self, drive: &mut DifferentialDrive<L, R>) -> Result<(), Error>
+    where
+        L: HBridgeDriver,
+        R: HBridgeDriver,
+    {
+        match self {
+            Self::Arcade { throttle, turn } => drive.arcade(throttle, turn),
+            Self::Tank { left, right } => drive.tank(left, right),
+            Self::EmergencyStop => drive.stop(StopMode::Brake),
+        }
+    }
+}
+
+/// Maps gamepad HID reports to [`DifferentialDrive`] inputs, with a slow mode toggle and a
+/// latching emergency stop.
+pub struct GamepadMapper {
+    style: DriveStyle,
+    stick: AnalogAxis,
+    trigger: AnalogAxis,
+    slow_mode_button: Option<u8>,
+    estop_button: Option<u8>,
+    slow_mode_scale: u16,
+    slow_mode: bool,
+    estop: bool,
+    last_buttons: u16,
+}
+
+impl GamepadMapper {
+
+    /// A mapper using `style` with no buttons assigned. Slow mode defaults to half speed.
+    pub fn new(style: DriveStyle) -> Self {
+        Self {
+            style,
+            stick: AnalogAxis::new(0, 128, u16::from(u8::MAX)),
+            trigger: AnalogAxis::new(0, 0, u16::from(u8::MAX)),
+            slow_mode_button: None,
+            estop_button: None,
+            slow_mode_scale: u16::MAX / 2,
+            slow_mode: false,
+            estop: false,
+            last_buttons: 0,
+        }
+    }
+
+    pub fn set_style(&mut self, style: DriveStyle) {
+        self.style = style;
+    }
+
+    /// Ignores stick readings within `deadband` of center
+    pub fn set_stick_deadband(&mut self, deadband: u8) {
+        self.stick.set_deadband(u16::from(deadband));
+    }
+
+    /// Each press of `button` toggles slow mode, `None` unassigns it
+    pub fn set_slow_mode_button(&mut self, button: Option<u8>) {
+        self.slow_mode_button = button;
+    }
+
+    /// Pressing `button` latches an emergency stop until [`GamepadMapper::clear_estop`],
+    /// `None` unassigns it
+    pub fn set_estop_button(&mut self, button: Option<u8>) {
+        self.estop_button = button;
+    }
+
+    /// Scales the inputs while in slow mode, as a fraction of full scale
+    pub fn set_slow_mode_scale(&mut self, scale: u16) {
+        self.slow_mode_scale = scale;
+    }
+
+    pub fn is_slow_mode(&self) -> bool {
+        self.slow_mode
+    }
+
+    pub fn is_estopped(&self) -> bool {
+        self.estop
+    }
+
+    pub fn clear_estop(&mut self) {
+        self.estop = false;
+    }
+
+    /// Decodes `report`, updating the button toggles
+    pub fn map(&mut self, report: &GamepadReport) -> GamepadInput {
+        let pressed = report.buttons & !self.last_buttons;
+        self.last_buttons = report.buttons;
+
+        let was_pressed = |button: Option<u8>| match button {
+            Some(button) => pressed.checked_shr(u32::from(button)).unwrap_or(0) & 1 != 0,
+            None => false,
+        };
+
+        if was_pressed(self.estop_button) {
+            self.estop = true;
+        }
+        if was_pressed(self.slow_mode_button) {
+            self.slow_mode = !self.slow_mode;
+        }
+
+        if self.estop {
+            return GamepadInput::EmergencyStop;
+        }
+
+        let x = |value: u8| self.scaled(self.stick.map(u16::from(value)));
+        // HID Y increases downwards, pushing the stick away is forwards
+        let y = |value: u8| -x(value);
+
+        match self.style {
+            DriveStyle::Arcade => GamepadInput::Arcade { throttle: y(report.left_y), turn: x(report.left_x) },
+            DriveStyle::SplitArcade => GamepadInput::Arcade { throttle: y(report.left_y), turn: x(report.right_x) },
+            DriveStyle::Tank => GamepadInput::Tank { left: y(report.left_y), right: y(report.right_y) },
+            DriveStyle::Triggers => {
+                let forward = self.trigger.map(u16::from(report.right_trigger));
+                let reverse = self.trigger.map(u16::from(report.left_trigger));
+                GamepadInput::Arcade { throttle: self.scaled(forward - reverse), turn: x(report.left_x) }
+            },
+        }
+    }
+
+    /// Applies the slow mode scale to `value`
+    fn scaled(&self, value: i32) -> i32 {
+        if self.slow_mode {
+            (i64::from(value) * i64::from(self.slow_mode_scale) / i64::from(u16::MAX)) as i32
+        } else {
+            value
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::{Telemetry, TimedCommand, Direction, Fault};
+
+/// What the telemetry must show while a [`HilStep`] is active
+#[derive(Clone, Default)]
+pub struct Expectation {
+    /// Frames within this long of the step's command are not checked, so the motor can
+    /// respond
+    pub settle_ms: u32,
+    /// Direction the motor must turn, judged by the sign of the measured speed
+    pub direction: Option<Direction>,
+    /// Measured speed in encoder counts/s
+    pub speed: Option<RangeInclusive<i32>>,
+    pub current_ma: Option<RangeInclusive<u32>>,
+    /// Whether any fault may be latched
+    pub allow_faults: bool,
+}
+
+/// A scripted command and the telemetry expected after it
+#[derive(Clone)]
+pub struct HilStep {
+    pub command: TimedCommand,
+    pub expect: Expectation,
+}
+
+/// Which part of an [`Expectation`] a frame broke
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    Direction,
+    Speed,
+    Current,
+    Fault,
+}
+
+/// A telemetry frame outside the active step's [`Expectation`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Violation {
+    /// Index of the active step
+    pub step: usize,
+    pub kind: ViolationKind,
+    pub telemetry: Telemetry,
+}
+
+/// Outcome of [`HilHarness::run`]
+#[derive(Default)]
+pub struct HilReport {
+    /// Valid telemetry frames received
+    pub frames: usize,
+    /// Bytes discarded while looking for frames, e.g. line noise or corrupt frames
+    pub discarded_bytes: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl HilReport {
+
+    /// Whether any frames arrived and none broke their expectation
+    pub fn passed(&self) -> bool {
+        self.frames > 0 && self.violations.is_empty()
+    }
+}
+
+/// Hardware-in-the-loop test harness, running a script of commands on a connected target
+/// and checking the [`Telemetry`] frames it streams back.
+///
+/// `link` is the target's telemetry stream, typically a serial port opened with a short
+/// read timeout. The crate has no command protocol, so commands are handed to `send`, which
+/// must forward them to the target, e.g. as lines of text its firmware parses.
+///
+/// Steps are timed by the host from the start of [`HilHarness::run`], frames are matched to
+/// the step active when they arrive.
+pub struct HilHarness<L, S>
+where
+    L: Read,
+    S: FnMut(&TimedCommand) -> io::Result<()>,
+{
+    link: L,
+    send: S,
+    buffer: Vec<u8>,
+}
+
+impl<L, S> HilHarness<L, S>
+where
+    L: Read,
+    S: FnMut(&TimedCommand) -> io::Result<()>,
+{
+
+    pub fn new(link: L, send: S) -> Self {
+        Self { link, send, buffer: Vec::new() }
+    }
+
+    /// Runs `steps`, sorted by time, then keeps checking telemetry against the last step
+    /// until `duration_ms` has passed since the start.
+    ///
+    /// Read timeouts are treated as no data, any other I/O error aborts the run.
+    pub fn run(&mut self, steps: &[HilStep], duration_ms: u32) -> io::Result<HilReport> {
+        let start = Instant::now();
+        let elapsed_ms = || start.elapsed().as_millis() as u32;
+
+        let mut report = HilReport::default();
+        let mut next = 0;
+        let mut active: Option<(usize, u32)> = None;
+        let mut chunk = [0u8; 64];
+
+        self.buffer.clear();
+
+        while elapsed_ms() < duration_ms {
+            while let Some(step) = steps.get(next)
+                && step.command.at_ms <= elapsed_ms()
+            {
+                (self.send)(&step.command)?;
+                active = Some((next, elapsed_ms()));
+                next += 1;
+            }
+
+            let read = match self.link.read(&mut chunk) {
+                Ok(read) => read,
+                Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => 0,
+                Err(e) => return Err(e),
+            };
+
+            if read == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            self.buffer.extend_from_slice(&chunk[..read]);
+
+            while let Some(telemetry) = self.next_frame(&mut report.discarded_bytes) {
+                report.frames += 1;
+
+                let Some((index, sent_ms)) = active else {
+                    continue;
+                };
+
+                let expect = &steps[index].expect;
+                if elapsed_ms().wrapping_sub(sent_ms) < expect.settle_ms {
+                    continue;
+                }
+
+                for kind in check(expect, &telemetry) {
+                    report.violations.push(Violation { step: index, kind, telemetry });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Releases the link and command sender.
+    pub fn free(self) -> (L, S) {
+        (self.link, self.send)
+    }
+
+    /// Takes the next valid frame off the front of the buffer, skipping anything that
+    /// doesn't decode
+    fn next_frame(&mut self, discarded: &mut usize) -> Option<Telemetry> {
+        loop {
+            let Some(sync) = self.buffer.windows(2).position(|w| w == [0xA5, 0x5A]) else {
+                // Keep a trailing first sync byte, the second may be in the next read
+                let keep = usize::from(self.buffer.last() == Some(&0xA5));
+                *discarded += self.buffer.len() - keep;
+                self.buffer.drain(..self.buffer.len() - keep);
+                return None;
+            };
+
+            *discarded += sync;
+            self.buffer.drain(..sync);
+
+            if self.buffer.len() < Telemetry::FRAME_LEN {
+                return None;
+            }
+
+            if let Some(telemetry) = Telemetry::decode(&self.buffer[..Telemetry::FRAME_LEN]) {
+                self.buffer.drain(..Telemetry::FRAME_LEN);
+                return Some(telemetry);
+            }
+
+            // A false sync, resynchronise from the next byte
+            *discarded += 1;
+            self.buffer.drain(..1);
+        }
+    }
+}
+
+/// The parts of `expect` that `telemetry` breaks
+fn check(expect: &Expectation, telemetry: &Telemetry) -> impl Iterator<Item = ViolationKind> {
+    let direction = expect.direction.is_some_and(|direction| match direction {
+        Direction::Forward => telemetry.speed <= 0,
+        Direction::Reverse => telemetry.speed >= 0,
+    });
+    let speed = expect.speed.as_ref().is_some_and(|range| !range.contains(&telemetry.speed));
+    let current = expect.current_ma.as_ref().is_some_and(|range| !range.contains(&telemetry.current_ma));
+    let fault = !expect.allow_faults && telemetry.faults != Fault::NONE;
+
+    [
+        (direction, ViolationKind::Direction),
+        (speed, ViolationKind::Speed),
+        (current, ViolationKind::Current),
+        (fault, ViolationKind::Fault),
+    ]
+    .into_iter()
+    .filter_map(|(broken, kind)| broken.then_some(kind))
+}
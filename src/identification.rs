@@ -0,0 +1,306 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::{IntoMillis, HBridgeDriver, CurrentSensor, EncoderReader, Command, Direction, StopMode, Error};
+
+/// Number of encoder samples taken while spinning up in [`identify`]
+const SPIN_UP_SAMPLES: usize = 64;
+
+/// Settings for [`identify`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdentificationSettings {
+    /// Motor supply voltage of the L298N in millivolts
+    pub supply_mv: u32,
+    /// Throttle of the pulse used to measure the winding resistance. Should be low enough
+    /// that the rotor barely moves during the pulse.
+    pub resistance_throttle: u16,
+    /// Length of the resistance measuring pulse in milliseconds
+    pub resistance_pulse_ms: u32,
+    /// Throttle of the step used to measure the speed constant and time constant
+    pub speed_throttle: u16,
+    /// Time given to reach a steady speed after the step, in milliseconds
+    pub spin_up_ms: u32,
+}
+
+impl IdentificationSettings {
+
+    pub fn new(
+        supply_mv: u32,
+        resistance_throttle: u16,
+        resistance_pulse: impl IntoMillis,
+        speed_throttle: u16,
+        spin_up: impl IntoMillis,
+    ) -> Self {
+        Self {
+            supply_mv,
+            resistance_throttle,
+            resistance_pulse_ms: resistance_pulse.into_millis(),
+            speed_throttle,
+            spin_up_ms: spin_up.into_millis(),
+        }
+    }
+}
+
+
+/// Motor parameters estimated by [`identify`], `None` where they couldn't be measured
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MotorParameters {
+    /// Winding resistance in milliohms
+    pub resistance_mohm: Option<u32>,
+    /// Speed constant in encoder counts per second per volt of back-EMF
+    pub kv: Option<u32>,
+    /// Mechanical time constant, the time to reach 63.2% of the steady speed, in milliseconds
+    pub time_constant_ms: Option<u32>,
+}
+
+/// Measures the winding resistance of `motor` by applying a short, low throttle pulse and
+/// reading the current at its end. Returns `None` if no current flowed.
+///
+/// For the most accurate result the rotor should be held still, as any back-EMF reduces the
+/// current and inflates the estimate.
+pub fn measure_resistance<M, S, D>(
+    motor: &mut M,
+    sensor: &mut S,
+    delay: &mut D,
+    supply_mv: u32,
+    throttle: u16,
+    pulse_ms: u32,
+) -> Result<Option<u32>, Error>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+    D: DelayNs,
+{
+    motor.set(Command::Drive { direction: Direction::Forward, throttle })?;
+    delay.delay_ms(pulse_ms);
+    let current_ma = sensor.current_ma();
+    motor.set(Command::Stop(StopMode::Coast))?;
+
+    if current_ma == 0 {
+        return Ok(None);
+    }
+
+    let applied_mv = applied_mv(supply_mv, throttle);
+    Ok(Some((applied_mv * 1000 / u64::from(current_ma)).min(u64::from(u32::MAX)) as u32))
+}
+
+/// Estimates the winding resistance, speed constant and mechanical time constant of `motor`.
+///
+/// The resistance is measured as in [`measure_resistance`]. The motor is then stepped to
+/// `speed_throttle` and the encoder speed recorded while it spins up, from which the time
+/// constant is found. The speed constant follows from the steady speed and the back-EMF
+/// left after the resistive drop. The motor is left coasting.
+pub fn identify<M, S, E, D>(
+    motor: &mut M,
+    sensor: &mut S,
+    encoder: &mut E,
+    delay: &mut D,
+    settings: IdentificationSettings,
+) -> Result<MotorParameters, Error>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+    E: EncoderReader,
+    D: DelayNs,
+{
+    let resistance_mohm = measure_resistance(
+        motor,
+        sensor,
+        delay,
+        settings.supply_mv,
+        settings.resistance_throttle,
+        settings.resistance_pulse_ms,
+    )?;
+
+    // Let the rotor settle after the resistance pulse
+    delay.delay_ms(settings.resistance_pulse_ms);
+
+    let sample_ms = (settings.spin_up_ms / SPIN_UP_SAMPLES as u32).max(1);
+    let mut speeds = [0i64; SPIN_UP_SAMPLES];
+
+    motor.set(Command::Drive { direction: Direction::Forward, throttle: settings.speed_throttle })?;
+    let mut last_count = encoder.count();
+    for speed in speeds.iter_mut() {
+        delay.delay_ms(sample_ms);
+        let count = encoder.count();
+        *speed = (count - last_count) * 1000 / i64::from(sample_ms);
+        last_count = count;
+    }
+    let current_ma = sensor.current_ma();
+    motor.set(Command::Stop(StopMode::Coast))?;
+
+    let steady = speeds[SPIN_UP_SAMPLES - 1].abs();
+    if steady == 0 {
+        return Ok(MotorParameters { resistance_mohm, kv: None, time_constant_ms: None });
+    }
+
+    // First sample at or above 63.2% of the steady speed
+    let time_constant_ms = speeds.iter()
+        .position(|speed| speed.abs() * 1000 >= steady * 632)
+        .map(|index| (index as u32 + 1) * sample_ms);
+
+    let kv = resistance_mohm.and_then(|resistance_mohm| {
+        let applied_mv = applied_mv(settings.supply_mv, settings.speed_throttle);
+        let drop_mv = u64::from(current_ma) * u64::from(resistance_mohm) / 1000;
+        let back_emf_mv = applied_mv.checked_sub(drop_mv).filter(|mv| *mv > 0)?;
+
+        Some((steady as u64 * 1000 / back_emf_mv).min(u64::from(u32::MAX)) as u32)
+    });
+
+    Ok(MotorParameters { resistance_mohm, kv, time_constant_ms })
+}
+
+/// Average voltage across the motor at `throttle`, in millivolts
+fn applied_mv(supply_mv: u32, throttle: u16) -> u64 {
+    u64::from(supply_mv) * u64::from(throttle) / u64::from(u16::MAX)
+}
+
+/// Number of encoder samples taken while coasting in [`coast_down`]
+const COAST_SAMPLES: usize = 64;
+
+/// Settings for [`coast_down`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoastDownSettings {
+    /// Throttle the motor is spun up to before coasting
+    pub throttle: u16,
+    /// Time given to reach a steady speed before coasting, in milliseconds
+    pub spin_up_ms: u32,
+    /// Longest the coast is recorded for, in milliseconds. Should be long enough for the
+    /// motor to stop.
+    pub max_coast_ms: u32,
+}
+
+impl CoastDownSettings {
+
+    pub fn new(throttle: u16, spin_up: impl IntoMillis, max_coast: impl IntoMillis) -> Self {
+        Self { throttle, spin_up_ms: spin_up.into_millis(), max_coast_ms: max_coast.into_millis() }
+    }
+}
+
+
+/// The speed decay of a coasting load, `deceleration = friction_decel + speed / tau`, found
+/// by [`coast_down`].
+///
+/// Without a torque measurement friction and inertia can't be separated, so both terms are
+/// decelerations: friction torque over inertia. A heavier load decays more slowly.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoastDown {
+    /// Speed when the coast started, in encoder counts/s
+    pub initial_speed: i32,
+    /// Constant (Coulomb) friction deceleration in counts/s²
+    pub friction_decel: u32,
+    /// Viscous friction time constant `tau` in milliseconds, `None` if viscous friction was
+    /// negligible
+    pub time_constant_ms: Option<u32>,
+    /// Time taken to stop, in milliseconds, `None` if still turning at the end of the coast
+    pub stop_time_ms: Option<u32>,
+}
+
+impl CoastDown {
+
+    /// Deceleration in counts/s² of the coasting load at `speed` (counts/s)
+    pub fn decel_at(&self, speed: i32) -> u32 {
+        let viscous = self.time_constant_ms
+            .map_or(0, |tau_ms| u64::from(speed.unsigned_abs()) * 1000 / u64::from(tau_ms.max(1)));
+
+        (u64::from(self.friction_decel) + viscous).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// A setpoint ramp, in counts/s per update at `update_hz`, matching how fast the load
+    /// slows on its own from its initial speed. Ramping down faster than this relies on
+    /// braking, ramping up much faster than it saturates the drive on heavy loads.
+    ///
+    /// Suitable for [`VelocityController::set_setpoint_ramp`].
+    ///
+    /// [`VelocityController::set_setpoint_ramp`]: crate::VelocityController::set_setpoint_ramp
+    pub fn suggested_setpoint_ramp(&self, update_hz: u32) -> u32 {
+        (self.decel_at(self.initial_speed) / update_hz.max(1)).max(1)
+    }
+}
+
+/// Spins `motor` up to `settings.throttle`, then coasts it, recording the encoder speed as
+/// it decays to estimate the load's friction and inertia, see [`CoastDown`].
+///
+/// The decay is fitted by least squares. Returns `None` if the motor didn't turn. The motor
+/// is left coasting.
+pub fn coast_down<M, E, D>(
+    motor: &mut M,
+    encoder: &mut E,
+    delay: &mut D,
+    settings: CoastDownSettings,
+) -> Result<Option<CoastDown>, Error>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+    D: DelayNs,
+{
+    let sample_ms = (settings.max_coast_ms / COAST_SAMPLES as u32).max(1);
+
+    motor.set(Command::Drive { direction: Direction::Forward, throttle: settings.throttle })?;
+    delay.delay_ms(settings.spin_up_ms.saturating_sub(sample_ms));
+    let start_count = encoder.count();
+    delay.delay_ms(sample_ms);
+    let mut last_count = encoder.count();
+    motor.set(Command::Stop(StopMode::Coast))?;
+
+    let initial_speed = (last_count - start_count) * 1000 / i64::from(sample_ms);
+    if initial_speed == 0 {
+        return Ok(None);
+    }
+
+    // Speeds are taken as positive in the direction of the spin up, to cope with an encoder
+    // counting backwards
+    let sign = initial_speed.signum();
+    let mut speeds = [0i64; COAST_SAMPLES + 1];
+    speeds[0] = initial_speed.abs();
+    let mut len = 1;
+    let mut stop_time_ms = None;
+
+    for (index, speed) in speeds.iter_mut().enumerate().skip(1) {
+        delay.delay_ms(sample_ms);
+        let count = encoder.count();
+        *speed = ((count - last_count) * sign * 1000 / i64::from(sample_ms)).max(0);
+        last_count = count;
+        len += 1;
+
+        if *speed == 0 {
+            stop_time_ms = Some(index as u32 * sample_ms);
+            break;
+        }
+    }
+
+    // Fit deceleration = c + k * speed between each pair of sampled (mean) speeds. The first
+    // sample is still under drive and the motor stops part way through the last interval,
+    // so neither is a full interval of coasting.
+    let fitted = if stop_time_ms.is_some() { len - 1 } else { len };
+    let (mut n, mut sum_v, mut sum_a, mut sum_vv, mut sum_va) = (0i128, 0i128, 0i128, 0i128, 0i128);
+    for pair in speeds[1..fitted].windows(2) {
+        let v = i128::from(pair[0] + pair[1]) / 2;
+        let a = i128::from(pair[0] - pair[1]) * 1000 / i128::from(sample_ms);
+
+        n += 1;
+        sum_v += v;
+        sum_a += a;
+        sum_vv += v * v;
+        sum_va += v * a;
+    }
+
+    let den = n * sum_vv - sum_v * sum_v;
+    let num = n * sum_va - sum_v * sum_a;
+
+    // k = num / den in 1/s, so tau = 1000 * den / num in ms. A non-positive k isn't physical,
+    // so the decay is taken as pure Coulomb friction.
+    let (time_constant_ms, friction_decel) = if den > 0 && num > 0 {
+        let tau_ms = (1000 * den / num).clamp(1, i128::from(u32::MAX));
+        let c = (sum_a * den - num * sum_v) / (n * den);
+        (Some(tau_ms as u32), c)
+    } else {
+        (None, if n > 0 { sum_a / n } else { 0 })
+    };
+
+    Ok(Some(CoastDown {
+        initial_speed: initial_speed.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32,
+        friction_decel: friction_decel.clamp(0, i128::from(u32::MAX)) as u32,
+        time_constant_ms,
+        stop_time_ms,
+    }))
+}
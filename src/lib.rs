@@ -1,9 +1,99 @@
 #![deny(unsafe_code)]
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 use embedded_hal::{digital, pwm};
+use embedded_hal::delay::DelayNs;
 use core::convert::Infallible;
 
+#[macro_use]
+mod macros;
+mod autotune;
+mod bank;
+mod calibration;
+mod clock;
+pub mod canopen;
+mod current;
+mod diagnostic;
+mod display;
+mod dual;
+mod encoder;
+mod error;
+mod fault;
+mod frequency;
+mod gamepad;
+#[cfg(feature = "std")]
+mod hil;
+mod identification;
+mod limit;
+mod manoeuvre;
+mod mixer;
+mod parallel;
+mod plugging;
+mod power;
+mod pid;
+mod runner;
+mod schedule;
+mod soak;
+mod softpwm;
+mod solenoid;
+mod stepper;
+mod supply;
+mod sync;
+mod telemetry;
+mod thermal;
+mod velocity;
+mod watchdog;
+#[cfg(feature = "ufmt")]
+mod ufmt_impls;
+#[cfg(feature = "units")]
+pub mod units;
+
+pub use autotune::{RelayTuneSettings, RelayTuneResult, relay_autotune};
+pub use bank::MotorBank;
+#[cfg(feature = "alloc")]
+pub use bank::DynMotorBank;
+pub use calibration::{Calibration, calibrate};
+pub use clock::{Clock, IntoMillis};
+#[cfg(feature = "fugit")]
+pub use clock::FugitClock;
+pub use canopen::{Cia402Node, Cia402State};
+pub use current::{CurrentSensor, CurrentFilter, FilteredCurrentSensor, TorqueController, ContinuousCurrentLimit};
+pub use diagnostic::{SelfTestSettings, WiringStatus, SelfTestReport, self_test_encoder, self_test_current};
+pub use dual::{DualCore, L298NDual, DualChannel};
+pub use encoder::{EncoderReader, VelocityEstimator, SpeedScale};
+pub use error::Error;
+pub use fault::{Fault, FaultPolicy, FaultMonitor};
+pub use frequency::{PwmFrequency, PwmFrequencyCheck, MAX_PWM_FREQUENCY_HZ, AUDIBLE_PWM_FREQUENCY_HZ};
+pub use gamepad::{GamepadReport, DriveStyle, GamepadInput, GamepadMapper};
+#[cfg(feature = "std")]
+pub use hil::{HilHarness, HilStep, HilReport, Expectation, Violation, ViolationKind};
+pub use identification::{IdentificationSettings, MotorParameters, CoastDownSettings, CoastDown, identify, measure_resistance, coast_down};
+pub use limit::{ThrottleLimit, ReversalLimit, SpeedGovernor, SoftStart, DutyBudget, DutyBudgetSettings};
+pub use manoeuvre::{DriveGeometry, DistanceDrive, Segment, PathFollower, SteeringGeometry, Twist};
+pub use mixer::{AnalogAxis, AxisConditioner, DifferentialDrive, HeadingHold, SlewLimit, StraightCorrection, TractionControl, FULL_SCALE};
+pub use parallel::L298NParallel;
+pub use plugging::{PluggingSettings, PluggingBrake, HoldMode, HillHold};
+pub use power::PowerRail;
+pub use pid::{Pid, PidGains, AntiWindup, GainSchedule, GAIN_FRAC_BITS};
+pub use runner::{ControlLoop, ControlRunner, ControlStep, StepInputs};
+pub use schedule::{MotorGroup, TimedCommand, CommandScheduler, CommandQueue, CommandRecorder, Maneuver};
+pub use soak::{SoakSettings, SoakGenerator};
+pub use softpwm::{L298NSlowPwm, SoftPwm, SoftPwmChannel, SoftPwmOutput};
+pub use solenoid::{Solenoid, SolenoidState};
+pub use stepper::{Stepper, StepMode, ClosedLoopStepper};
+pub use supply::UnderVoltageLockout;
+pub use sync::SpeedFollower;
+pub use telemetry::{Telemetry, TelemetrySink, BlackBox, FrameEncoder, CommandHistory};
+pub use thermal::OverTemperatureGuard;
+pub use velocity::{VelocityController, VelocityProfile, ProfileSet};
+pub use watchdog::{WatchdogFeed, CommandWatchdog};
+
 /// Driver wrapper for one half of an **L298N** Dual Full-Bridge.
 ///
 /// ## Wiring
@@ -22,6 +112,28 @@ use core::convert::Infallible;
 ///
 /// A enable pin set to low result in a Free Running Motor Stop.
 ///
+/// ## Output model
+/// The driver tracks three things separately: the commanded motion
+/// ([`L298NHBridge::last_command`]), the applied direction inputs
+/// ([`L298NHBridge::get_inputs`]) and the applied enable duty
+/// ([`L298NHBridge::get_enable_duty`]). A brake holds the enable fully on, but reports a
+/// throttle of zero, and a stop keeps the last drive throttle for resuming
+/// ([`L298NHBridge::get_drive_throttle`]).
+///
+/// ## Hot path
+/// The driver remembers which state the inputs are set to, so a [`Command::Drive`] in
+/// the current direction only writes the duty cycle. A change of direction adds two pin
+/// writes, so the worst case of [`L298NHBridge::set`] is two GPIO writes plus one duty cycle
+/// write, with no loops or divisions beyond the duty scaling. This keeps it suitable for
+/// calling at multi-kHz control rates from an interrupt. See the `bench_set` example for
+/// measuring the cycle counts on a Cortex-M target. [`L298NHBridge::set_timed`] trades
+/// some of this for guaranteed switching margins around direction changes.
+///
+/// ## Borrowed pins
+/// The pins and PWM may be `&mut` references, which embedded-hal implements the traits for,
+/// so a bridge can be built around pins only for as long as it is needed, e.g. in a test
+/// phase, and dropped or [freed](L298NHBridge::free) to use them for something else.
+///
 /// ## Type Parameters
 /// - `P1, N1`: GPIO port letter and pin number for `dir1`.
 /// - `P2, N2`: GPIO port letter and pin number for `dir2`.
@@ -36,23 +148,165 @@ where
     dir1: P1,
     dir2: P2,
     enable: EN,
-    throttle: u16,
+    command: Command,
+    /// Throttle of the last drive command, kept while stopped
+    drive_throttle: u16,
+    /// The state the direction inputs are set to, `None` until first written
+    inputs: Option<BridgeInputs>,
+    /// Duty cycle applied to the enable output
+    duty: u16,
+    pwm_frequency_hz: Option<u32>,
+    /// Whether the outputs have been put into the safe state, drive commands are rejected
+    /// until then
+    initialized: bool,
+    /// Margin kept around direction input changes by [`L298NHBridge::set_timed`]
+    input_margin_ns: u32,
+}
+
+/// States of the direction inputs of one bridge
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum BridgeInputs {
+    /// dir1 high, dir2 low
+    Forward,
+    /// dir1 low, dir2 high
+    Reverse,
+    /// Both high, a fast motor stop while enabled
+    Brake,
+    /// Both low
+    Off,
+}
+
+/// The outputs of one bridge, to tell which failed
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum BridgeOutput { Dir1, Dir2 }
+
+impl BridgeInputs {
+    /// Levels of dir1 and dir2, high is `true`
+    pub(crate) fn levels(self) -> (bool, bool) {
+        match self {
+            BridgeInputs::Forward => (true, false),
+            BridgeInputs::Reverse => (false, true),
+            BridgeInputs::Brake => (true, true),
+            BridgeInputs::Off => (false, false),
+        }
+    }
 }
 
 /// A `Command` sent to a motor driver
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 pub enum Command {
     Drive { direction: Direction, throttle: u16 },
     Stop(StopMode),
 }
 
 /// The direction of the H-Bridge
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 pub enum Direction { Forward, Reverse }
 
 /// Each `StopMode` variant maps to a stop mode specified in the datasheet:
 /// - Brake -> Fast Motor Stop
 /// - Coast -> Free Running Motor Stop
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 pub enum StopMode { Brake, Coast }
 
+/// Common interface of the motor drivers in this crate, so they can be used interchangeably,
+/// e.g. in a [`MotorBank`].
+pub trait HBridgeDriver {
+    /// Applies `cmd`. A driver that refuses it returns the reason, e.g. [`Error::Disarmed`]
+    /// before initialization or [`Error::Faulted`] while a fault is latched, and
+    /// [`HBridgeDriver::last_command`] and [`HBridgeDriver::get_throttle`] keep reporting
+    /// what was actually applied rather than `cmd`.
+    fn set(&mut self, cmd: Command) -> Result<(), Error>;
+    fn get_throttle(&self) -> u16;
+
+    /// Returns the driver to a safe, coasting state and clears any transient faults, so an
+    /// application can recover without reconstructing it. Defaults to coasting.
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.set(Command::Stop(StopMode::Coast))
+    }
+
+    /// Faults currently latched by this driver or any driver it wraps. Defaults to none.
+    fn latched_faults(&self) -> Fault {
+        Fault::NONE
+    }
+
+    /// The PWM frequency of the enable output, if it has been reported. Defaults to `None`.
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        None
+    }
+
+    /// The command most recently requested of the driver, if it keeps track. Wrappers report
+    /// the command they passed on, see [`CommandHistory`] for recording what was requested of
+    /// a wrapper itself. Defaults to `None`.
+    fn last_command(&self) -> Option<Command> {
+        None
+    }
+}
+
+impl<T> HBridgeDriver for &mut T
+where
+    T: HBridgeDriver + ?Sized,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        (**self).set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        (**self).get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        (**self).reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        (**self).latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        (**self).pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        (**self).last_command()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> HBridgeDriver for alloc::boxed::Box<T>
+where
+    T: HBridgeDriver + ?Sized,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        (**self).set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        (**self).get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        (**self).reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        (**self).latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        (**self).pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        (**self).last_command()
+    }
+}
+
 impl<P1, P2, EN> L298NHBridge<P1, P2, EN>
 where 
     P1: digital::OutputPin<Error = Infallible>,
@@ -60,22 +314,78 @@ where
     EN: pwm::SetDutyCycle<Error = Infallible>,
 {
 
-    pub fn new(dir1: P1, dir2: P2, enable: EN) -> Result<Self,Infallible> {
-        let mut  handle = Self { dir1, dir2, enable, throttle: 0u16 };
-        handle.enable.set_duty_cycle(0u16)?;
+    /// Takes the pins and puts the bridge into the safe state, whatever the HAL left them in:
+    /// enable low first, then both direction inputs low.
+    pub fn new(dir1: P1, dir2: P2, enable: EN) -> Result<Self, Error> {
+        let mut  handle = Self::new_unconfigured(dir1, dir2, enable);
+        handle.init()?;
+
+        Ok(handle)
+    }
+
+    /// Takes the pins without touching the hardware, e.g. for static construction or when
+    /// the PWM timer isn't running yet. Drive commands are rejected with [`Error::Disarmed`]
+    /// until [`L298NHBridge::init`], stops are ignored.
+    pub const fn new_unconfigured(dir1: P1, dir2: P2, enable: EN) -> Self {
+        Self {
+            dir1, dir2, enable,
+            command: Command::Stop(StopMode::Coast),
+            drive_throttle: 0u16,
+            inputs: None,
+            duty: 0u16,
+            pwm_frequency_hz: None,
+            initialized: false,
+            input_margin_ns: DEFAULT_INPUT_MARGIN_NS,
+        }
+    }
+
+    /// Puts the bridge into the safe state and starts accepting commands
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.reinit()?;
+        self.initialized = true;
+
+        Ok(())
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Like [`L298NHBridge::new`], then reads the direction inputs back to check they went
+    /// low, e.g. to catch a pin stuck by a short on a production test fixture.
+    pub fn new_verified(dir1: P1, dir2: P2, enable: EN) -> Result<Self, Error>
+    where
+        P1: digital::StatefulOutputPin,
+        P2: digital::StatefulOutputPin,
+    {
+        let mut handle = Self::new(dir1, dir2, enable)?;
+
+        if !handle.dir1.is_set_low()? {
+            return Err(Error::VerifyFailed(BridgeOutput::Dir1));
+        }
+        if !handle.dir2.is_set_low()? {
+            return Err(Error::VerifyFailed(BridgeOutput::Dir2));
+        }
 
         Ok(handle)
     }
 
-    pub fn set(&mut self, cmd: Command) -> Result<(), Infallible> {
+    /// Applies `cmd`. Until the bridge is initialized, drive commands are rejected with
+    /// [`Error::Disarmed`] and stops are ignored, the outputs already being off.
+    pub fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        if !self.initialized {
+            return disarmed(cmd);
+        }
+
         match cmd {
             Command::Drive { direction, throttle } => {
                 match direction {
-                    Direction::Forward => self.forward()?,
-                    Direction::Reverse => self.reverse()?,
+                    Direction::Forward => self.set_inputs(BridgeInputs::Forward)?,
+                    Direction::Reverse => self.set_inputs(BridgeInputs::Reverse)?,
                 }
 
-                self.set_throttle(throttle)?;
+                self.drive_throttle = throttle;
+                self.set_duty(duty_from_fullscale(self.enable.max_duty_cycle(), throttle))?;
             },
             Command::Stop(stop_mode) => {
                 match stop_mode {
@@ -84,57 +394,223 @@ where
                 }
             }
         }
+        self.command = cmd;
+
         Ok(())
     }
 
+    /// Like [`L298NHBridge::set`], but when the direction inputs change, the enable is pulled
+    /// low and the inputs are changed with the input margin on either side. This keeps the
+    /// datasheet's switching times at high PWM frequencies, where an enable pulse could
+    /// otherwise overlap the inputs settling.
+    pub fn set_timed<D>(&mut self, cmd: Command, delay: &mut D) -> Result<(), Error>
+    where
+        D: DelayNs,
+    {
+        if !self.initialized {
+            return disarmed(cmd);
+        }
+
+        let inputs = match cmd {
+            Command::Drive { direction: Direction::Forward, .. } => Some(BridgeInputs::Forward),
+            Command::Drive { direction: Direction::Reverse, .. } => Some(BridgeInputs::Reverse),
+            Command::Stop(StopMode::Brake) => Some(BridgeInputs::Brake),
+            Command::Stop(StopMode::Coast) => None,
+        };
+
+        if let Some(inputs) = inputs
+            && self.inputs != Some(inputs)
+        {
+            self.set_duty(0u16)?;
+            delay.delay_ns(self.input_margin_ns);
+            self.set_inputs(inputs)?;
+            delay.delay_ns(self.input_margin_ns);
+        }
+
+        self.set(cmd)
+    }
+
+    /// Sets the margin [`L298NHBridge::set_timed`] keeps around input changes, 2 µs by
+    /// default
+    pub fn set_input_margin_ns(&mut self, margin_ns: u32) {
+        self.input_margin_ns = margin_ns;
+    }
+
+    /// The commanded drive throttle, zero while stopped
     pub fn get_throttle(&self) -> u16 {
-        self.throttle
+        match self.command {
+            Command::Drive { throttle, .. } => throttle,
+            Command::Stop(_) => 0u16,
+        }
     }
 
-    fn set_throttle(&mut self, throttle: u16) -> Result<(), Infallible> {
-        self.throttle = throttle;
+    /// The throttle of the last drive command, which is kept while stopped so it can be
+    /// resumed
+    pub fn get_drive_throttle(&self) -> u16 {
+        self.drive_throttle
+    }
 
-        let duty = self.duty_from_fullscale(throttle);
-        self.enable.set_duty_cycle(duty)?;
+    /// The motion last commanded
+    pub fn last_command(&self) -> Command {
+        self.command
+    }
+
+    /// The state the direction inputs are set to, `None` if they haven't been written since
+    /// construction
+    pub fn get_inputs(&self) -> Option<BridgeInputs> {
+        self.inputs
+    }
+
+    /// The duty cycle applied to the enable output, in the PWM's own units
+    pub fn get_enable_duty(&self) -> u16 {
+        self.duty
+    }
+
+    /// Re-applies the safe state: enable low, then both direction inputs low.
+    ///
+    /// The PWM's max duty is read on every throttle change, so a reconfigured timer is picked
+    /// up without further action.
+    pub fn reinit(&mut self) -> Result<(), Error> {
+        self.set_duty(0u16)?;
+        self.inputs = None;
+        self.set_inputs(BridgeInputs::Off)?;
+        self.command = Command::Stop(StopMode::Coast);
+        self.drive_throttle = 0u16;
 
         Ok(())
     }
 
-    fn duty_from_fullscale(&self, throttle: u16) -> u16 {
-        let max = self.enable.max_duty_cycle() as u32;
-        let throttle = throttle as u32;
+    /// Records the PWM frequency of the enable output and checks it against the L298's
+    /// recommended range. Call again whenever the timer is reconfigured.
+    pub fn set_pwm_frequency_hz(&mut self, frequency_hz: u32) -> PwmFrequencyCheck {
+        self.pwm_frequency_hz = Some(frequency_hz);
+        PwmFrequencyCheck::check(frequency_hz)
+    }
+
+    /// Reads the PWM frequency from the enable output, see
+    /// [`L298NHBridge::set_pwm_frequency_hz`].
+    pub fn report_pwm_frequency(&mut self) -> PwmFrequencyCheck
+    where
+        EN: PwmFrequency,
+    {
+        let frequency_hz = self.enable.pwm_frequency_hz();
+        self.set_pwm_frequency_hz(frequency_hz)
+    }
+
+    /// The PWM frequency last reported, if any
+    pub fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.pwm_frequency_hz
+    }
+
+    /// The enable PWM's maximum duty cycle, i.e. full on
+    pub fn max_duty(&self) -> u16 {
+        self.enable.max_duty_cycle()
+    }
+
+    /// Writes `duty`, in the PWM's own units and capped at [`L298NHBridge::max_duty`], straight
+    /// to the enable output, bypassing the throttle model. The direction inputs and the
+    /// commanded motion are left as they are. Rejected with [`Error::Disarmed`] until the
+    /// bridge is initialized.
+    pub fn set_raw_duty(&mut self, duty: u16) -> Result<(), Error> {
+        if !self.initialized {
+            return Err(Error::Disarmed);
+        }
 
-        ((max * throttle + 0x8000) / 0xFFFF) as u16
+        self.set_duty(duty.min(self.enable.max_duty_cycle()))
     }
-    
-    /// Sets the L298 into forward mode
-    fn forward(&mut self) -> Result<(), Infallible> {
-        self.dir1.set_high()?;
-        self.dir2.set_low()?;
+
+    /// Releases the pins and PWM, which are left as they are. Call
+    /// [`L298NHBridge::reinit`] first to hand them over in the safe state.
+    pub fn free(self) -> (P1, P2, EN) {
+        (self.dir1, self.dir2, self.enable)
+    }
+
+    fn set_duty(&mut self, duty: u16) -> Result<(), Error> {
+        self.enable.set_duty_cycle(duty)?;
+        self.duty = duty;
 
         Ok(())
     }
 
-    /// Sets the L298 into reverse mode
-    fn reverse(&mut self) -> Result<(), Infallible> {
-        self.dir1.set_low()?;
-        self.dir2.set_high()?;
+    /// Sets the direction inputs, skipping the writes if they are already in that state
+    fn set_inputs(&mut self, inputs: BridgeInputs) -> Result<(), Error> {
+        if self.inputs == Some(inputs) {
+            return Ok(());
+        }
+
+        let (dir1, dir2) = inputs.levels();
+        self.dir1.set_state(dir1.into())?;
+        self.dir2.set_state(dir2.into())?;
+        self.inputs = Some(inputs);
 
         Ok(())
     }
 
     /// Sets the L298 into fast motor stop mode
-    fn fast_motor_stop(&mut self) -> Result<(), Infallible> {
-        self.dir1.set_high()?;
-        self.dir2.set_high()?;
-        self.set_throttle(u16::MAX)?;
+    fn fast_motor_stop(&mut self) -> Result<(), Error> {
+        self.set_inputs(BridgeInputs::Brake)?;
+        self.set_duty(self.enable.max_duty_cycle())
+    }
 
-        Ok(())
+    /// Sets the L298 into free running motor stop mode, leaving the inputs as they are
+    fn free_running_motor_stop(&mut self) -> Result<(), Error> {
+        self.set_duty(0u16)
     }
+}
 
-    /// Sets the L298 into free running motor stop mode
-    fn free_running_motor_stop(&mut self) -> Result<(), Infallible> {
-        self.set_throttle(0u16)?;
-        Ok(())
+impl<P1, P2, EN> HBridgeDriver for L298NHBridge<P1, P2, EN>
+where 
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: pwm::SetDutyCycle<Error = Infallible>,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        L298NHBridge::set(self, cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        L298NHBridge::get_throttle(self)
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        L298NHBridge::reinit(self)
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        L298NHBridge::pwm_frequency_hz(self)
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        Some(L298NHBridge::last_command(self))
+    }
+}
+
+/// Default margin kept around direction input changes, covering the L298's worst case
+/// input to output delays
+const DEFAULT_INPUT_MARGIN_NS: u32 = 2_000;
+
+/// What an uninitialized bridge makes of `cmd`: drive commands are rejected, stops ignored
+fn disarmed(cmd: Command) -> Result<(), Error> {
+    match cmd {
+        Command::Drive { .. } => Err(Error::Disarmed),
+        Command::Stop(_) => Ok(()),
     }
 }
+
+/// Converts a full scale `throttle` to a rounded percentage
+pub(crate) fn throttle_percent(throttle: u16) -> u8 {
+    // At most 0xFFFF * 100 + 0x7FFF, well within u32, and the quotient is at most 100
+    ((u32::from(throttle) * 100 + 0x7FFF) / 0xFFFF) as u8
+}
+
+/// Scales a full scale `throttle` (0..=u16::MAX) to a duty cycle in 0..=`max_duty`, rounded
+/// to nearest. Used over `set_duty_cycle_fraction`, which truncates, so every driver applies
+/// the same duty for the same throttle.
+pub(crate) fn duty_from_fullscale(max_duty: u16, throttle: u16) -> u16 {
+    let max = u32::from(max_duty);
+    let throttle = u32::from(throttle);
+
+    // Cannot overflow: 0xFFFF * 0xFFFF + 0x8000 = 0xFFFE_8001 < u32::MAX. The divisor is a
+    // non-zero constant and the quotient is at most `max_duty`, so the cast is lossless.
+    ((max * throttle + 0x8000) / 0xFFFF) as u16
+}
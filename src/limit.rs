@@ -0,0 +1,648 @@
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, Direction, StopMode, Fault, Error};
+
+/// Caps the throttle of a motor driver, e.g. to keep an over-volted motor within its rating.
+///
+/// Commands above the cap are clamped to it and counted as saturated, so a closed loop
+/// layer above can see its actuator saturating, e.g. to stop integrating, rather than
+/// silently winding up.
+pub struct ThrottleLimit<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    max_throttle: u16,
+    saturated: bool,
+    saturation_count: u32,
+}
+
+impl<M> ThrottleLimit<M>
+where
+    M: HBridgeDriver,
+{
+
+    pub fn new(motor: M, max_throttle: u16) -> Self {
+        Self { motor, max_throttle, saturated: false, saturation_count: 0 }
+    }
+
+    /// Sets the cap, taking effect from the next command
+    pub fn set_max_throttle(&mut self, max_throttle: u16) {
+        self.max_throttle = max_throttle;
+    }
+
+    pub fn get_max_throttle(&self) -> u16 {
+        self.max_throttle
+    }
+
+    /// Whether the last command was clamped
+    pub fn is_saturated(&self) -> bool {
+        self.saturated
+    }
+
+    /// Number of commands clamped since the last [`ThrottleLimit::reset_saturation_count`],
+    /// saturating at `u32::MAX`
+    pub fn saturation_count(&self) -> u32 {
+        self.saturation_count
+    }
+
+    pub fn reset_saturation_count(&mut self) {
+        self.saturation_count = 0;
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M> HBridgeDriver for ThrottleLimit<M>
+where
+    M: HBridgeDriver,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        let cmd = match cmd {
+            Command::Drive { direction, throttle } if throttle > self.max_throttle => {
+                self.saturated = true;
+                self.saturation_count = self.saturation_count.saturating_add(1);
+                Command::Drive { direction, throttle: self.max_throttle }
+            }
+            cmd => {
+                self.saturated = false;
+                cmd
+            }
+        };
+
+        self.motor.set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.saturated = false;
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
+
+/// Limits how often a motor driver may reverse, so a chattering control loop can't hammer
+/// the gearbox and bridge with rapid direction changes.
+///
+/// A reversal needs the motor to have been stopped for at least the dwell time, and the
+/// previous reversal to be at least the minimum interval ago. Until then the motor coasts
+/// and the reversing command is deferred, later commands replacing it, and applied by
+/// [`ReversalLimit::poll`] once allowed. A command back in the original direction cancels it.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct ReversalLimit<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    min_dwell_ms: u32,
+    min_interval_ms: u32,
+    /// Direction the motor was last driven in, even if stopped since
+    direction: Option<Direction>,
+    /// When the motor was stopped, `None` while driving
+    stopped_ms: Option<u32>,
+    last_reversal_ms: Option<u32>,
+    pending: Option<Command>,
+    deferred_count: u32,
+}
+
+impl<M> ReversalLimit<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Wraps `motor`, requiring `min_dwell` stopped before, and `min_interval` between,
+    /// reversals. Either may be zero.
+    pub fn new(motor: M, min_dwell: impl IntoMillis, min_interval: impl IntoMillis) -> Self {
+        Self {
+            motor,
+            min_dwell_ms: min_dwell.into_millis(),
+            min_interval_ms: min_interval.into_millis(),
+            direction: None,
+            stopped_ms: None,
+            last_reversal_ms: None,
+            pending: None,
+            deferred_count: 0,
+        }
+    }
+
+    /// Limits reversals to `rate` per second, as a minimum interval between them
+    pub fn set_max_reversals_per_sec(&mut self, rate: u32) {
+        self.min_interval_ms = 1000u32.checked_div(rate).unwrap_or(0);
+    }
+
+    pub fn set_min_dwell(&mut self, min_dwell: impl IntoMillis) {
+        self.min_dwell_ms = min_dwell.into_millis();
+    }
+
+    pub fn set_min_interval(&mut self, min_interval: impl IntoMillis) {
+        self.min_interval_ms = min_interval.into_millis();
+    }
+
+    /// Sends `cmd` to the motor, or defers it if it is a reversal that isn't allowed yet.
+    pub fn set(&mut self, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        match cmd {
+            Command::Stop(_) => {
+                self.pending = None;
+                self.stopped_ms.get_or_insert(now_ms);
+                self.motor.set(cmd)
+            }
+            Command::Drive { direction, .. } if self.direction.is_none_or(|d| d == direction) => {
+                self.pending = None;
+                self.drive(cmd, direction)
+            }
+            Command::Drive { direction, .. } => {
+                if self.reversal_allowed(now_ms) {
+                    self.pending = None;
+                    self.last_reversal_ms = Some(now_ms);
+                    return self.drive(cmd, direction);
+                }
+
+                self.pending = Some(cmd);
+                self.deferred_count = self.deferred_count.saturating_add(1);
+
+                if self.stopped_ms.is_none() {
+                    self.stopped_ms = Some(now_ms);
+                    self.motor.set(Command::Stop(StopMode::Coast))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies a deferred reversal once it is allowed. Must be called at least as often as
+    /// the timing resolution needed.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        if let Some(cmd) = self.pending
+            && self.reversal_allowed(now_ms)
+        {
+            self.set(cmd, &now_ms)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a reversal is waiting to be applied
+    pub fn is_deferring(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Number of reversing commands deferred, saturating at `u32::MAX`. Commands replacing
+    /// one already deferred count too.
+    pub fn deferred_count(&self) -> u32 {
+        self.deferred_count
+    }
+
+    pub fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+
+    fn drive(&mut self, cmd: Command, direction: Direction) -> Result<(), Error> {
+        self.direction = Some(direction);
+        self.stopped_ms = None;
+        self.motor.set(cmd)
+    }
+
+    fn reversal_allowed(&self, now_ms: u32) -> bool {
+        let dwelt = self.min_dwell_ms == 0
+            || self.stopped_ms.is_some_and(|stopped_ms| now_ms.wrapping_sub(stopped_ms) >= self.min_dwell_ms);
+        let spaced = self.last_reversal_ms
+            .is_none_or(|reversal_ms| now_ms.wrapping_sub(reversal_ms) >= self.min_interval_ms);
+
+        dwelt && spaced
+    }
+}
+
+/// Enforces a hard speed ceiling on a motor driver, independent of whatever controls it,
+/// e.g. as a safety requirement for robots around people.
+///
+/// Fed with the measured speed through [`SpeedGovernor::report_speed`], it coasts the motor
+/// whenever the speed exceeds the ceiling, reporting [`Fault::OVER_SPEED`], until it drops
+/// the hysteresis below it. The last command received is then applied again. Each trip is
+/// counted.
+///
+/// Sits below a controller, e.g. `VelocityController<SpeedGovernor<M>, E>`, fed from
+/// [`VelocityController::speed`] through [`VelocityController::motor_mut`], or better from a
+/// second speed sensor.
+///
+/// [`VelocityController::speed`]: crate::VelocityController::speed
+/// [`VelocityController::motor_mut`]: crate::VelocityController::motor_mut
+pub struct SpeedGovernor<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    ceiling: u32,
+    hysteresis: u32,
+    governing: bool,
+    trip_count: u32,
+    /// Last command received, applied again once the speed is back under the ceiling
+    command: Option<Command>,
+}
+
+impl<M> SpeedGovernor<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Wraps `motor`, limiting its speed to `ceiling` counts/s in either direction
+    pub fn new(motor: M, ceiling: u32, hysteresis: u32) -> Self {
+        Self { motor, ceiling, hysteresis, governing: false, trip_count: 0, command: None }
+    }
+
+    pub fn set_ceiling(&mut self, ceiling: u32, hysteresis: u32) {
+        self.ceiling = ceiling;
+        self.hysteresis = hysteresis;
+    }
+
+    /// Checks the measured `speed` in counts/s against the ceiling. Must be called every
+    /// time the speed is measured.
+    pub fn report_speed(&mut self, speed: i32) -> Result<(), Error> {
+        let speed = speed.unsigned_abs();
+
+        if !self.governing && speed > self.ceiling {
+            self.governing = true;
+            self.trip_count = self.trip_count.saturating_add(1);
+            return self.motor.set(Command::Stop(StopMode::Coast));
+        }
+
+        if self.governing && speed <= self.ceiling.saturating_sub(self.hysteresis) {
+            self.governing = false;
+            if let Some(cmd) = self.command {
+                return self.motor.set(cmd);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the motor is coasting because it exceeded the ceiling
+    pub fn is_governing(&self) -> bool {
+        self.governing
+    }
+
+    /// Number of times the ceiling has been exceeded, saturating at `u32::MAX`
+    pub fn trip_count(&self) -> u32 {
+        self.trip_count
+    }
+
+    pub fn reset_trip_count(&mut self) {
+        self.trip_count = 0;
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M> HBridgeDriver for SpeedGovernor<M>
+where
+    M: HBridgeDriver,
+{
+    /// Passes `cmd` to the motor, holding back drive commands while governing
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        self.command = Some(cmd);
+
+        match cmd {
+            Command::Drive { .. } if self.governing => Ok(()),
+            cmd => self.motor.set(cmd),
+        }
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.governing = false;
+        self.command = None;
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        let governing = if self.governing { Fault::OVER_SPEED } else { Fault::NONE };
+        self.motor.latched_faults() | governing
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
+
+/// Soft-starts a motor driver, capping the throttle for a while after each start from a
+/// standstill (or reversal), to limit the inrush current through the L298N.
+///
+/// The cap rises linearly from the start throttle to full throttle over the window, then
+/// is lifted. This is independent of any slew limiting applied before the driver: it acts
+/// only on starts, however the throttle got there. A standstill is judged from the
+/// commands, a stop or zero throttle.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct SoftStart<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    window_ms: u32,
+    start_throttle: u16,
+    /// Last drive command requested, `None` while stopped
+    command: Option<(Direction, u16)>,
+    /// When the current start began, `None` once the window has passed
+    started_ms: Option<u32>,
+}
+
+impl<M> SoftStart<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Wraps `motor`, limiting starts to `start_throttle` rising to full throttle over
+    /// `window`
+    pub fn new(motor: M, window: impl IntoMillis, start_throttle: u16) -> Self {
+        Self { motor, window_ms: window.into_millis(), start_throttle, command: None, started_ms: None }
+    }
+
+    pub fn set_window(&mut self, window: impl IntoMillis, start_throttle: u16) {
+        self.window_ms = window.into_millis();
+        self.start_throttle = start_throttle;
+    }
+
+    /// Sends `cmd` to the motor, capping its throttle if it starts the motor.
+    pub fn set(&mut self, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        match cmd {
+            Command::Drive { direction, throttle } if throttle != 0 => {
+                if self.command.is_none_or(|(last, _)| last != direction) {
+                    self.started_ms = Some(now_ms);
+                }
+
+                self.command = Some((direction, throttle));
+                self.apply(now_ms)
+            }
+            cmd => {
+                self.command = None;
+                self.started_ms = None;
+                self.motor.set(cmd)
+            }
+        }
+    }
+
+    /// Raises the cap as the window passes. Must be called regularly while starting, at
+    /// least as often as the smoothness of the ramp needs.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        if self.started_ms.is_some() {
+            self.apply(now_ms)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a start is in progress and the throttle capped
+    pub fn is_limiting(&self) -> bool {
+        self.started_ms.is_some()
+    }
+
+    pub fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+
+    fn apply(&mut self, now_ms: u32) -> Result<(), Error> {
+        let Some((direction, throttle)) = self.command else {
+            return Ok(());
+        };
+
+        let cap = match self.started_ms {
+            Some(started_ms) if now_ms.wrapping_sub(started_ms) < self.window_ms => {
+                let elapsed = u64::from(now_ms.wrapping_sub(started_ms));
+                let rise = u64::from(u16::MAX - self.start_throttle) * elapsed / u64::from(self.window_ms);
+                self.start_throttle + rise as u16
+            }
+            _ => {
+                self.started_ms = None;
+                u16::MAX
+            }
+        };
+
+        self.motor.set(Command::Drive { direction, throttle: throttle.min(cap) })
+    }
+}
+
+/// Settings for a [`DutyBudget`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DutyBudgetSettings {
+    /// Throttles above this count against the budget
+    pub threshold: u16,
+    /// Time the throttle may spend above the threshold per window, in milliseconds
+    pub budget_ms: u32,
+    /// Window the budget applies to, in milliseconds. Must be longer than the budget.
+    pub window_ms: u32,
+    /// Throttle drive commands are capped to once the budget is spent
+    pub derated_throttle: u16,
+}
+
+impl DutyBudgetSettings {
+
+    /// Allows `budget` above `threshold` per `window`, derating to `derated_throttle`
+    pub fn new(threshold: u16, budget: impl IntoMillis, window: impl IntoMillis, derated_throttle: u16) -> Self {
+        Self { threshold, budget_ms: budget.into_millis(), window_ms: window.into_millis(), derated_throttle }
+    }
+}
+
+
+/// At most 10 s above 90% throttle per minute, derating to half throttle
+impl Default for DutyBudgetSettings {
+    fn default() -> Self {
+        Self {
+            threshold: u16::MAX / 10 * 9,
+            budget_ms: 10_000,
+            window_ms: 60_000,
+            derated_throttle: u16::MAX / 2,
+        }
+    }
+}
+
+/// Limits how long a motor driver may run near full throttle, protecting the L298N and small
+/// motors from sustained overload without needing current sensing.
+///
+/// Time spent above the threshold fills a bucket, which drains while below it, such that a
+/// full budget recovers over the rest of the window: with the defaults, 10 s above 90%
+/// throttle needs 50 s below it. Once the bucket is full, drive commands are capped to the
+/// derated throttle until it has drained empty again.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct DutyBudget<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    settings: DutyBudgetSettings,
+    /// Last command requested
+    command: Option<Command>,
+    /// Bucket level, in milliseconds scaled by the window less the budget
+    level: u64,
+    last_ms: Option<u32>,
+    derated: bool,
+    trip_count: u32,
+}
+
+impl<M> DutyBudget<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Wraps `motor`, failing with [`Error::ConfigInvalid`] unless `settings.budget_ms` is
+    /// nonzero and less than `settings.window_ms`.
+    pub fn new(motor: M, settings: DutyBudgetSettings) -> Result<Self, Error> {
+        check_budget(&settings)?;
+
+        Ok(Self { motor, settings, command: None, level: 0, last_ms: None, derated: false, trip_count: 0 })
+    }
+
+    /// Replaces the settings, emptying the bucket. Invalid settings are rejected as in
+    /// [`DutyBudget::new`], keeping the previous ones.
+    pub fn set_settings(&mut self, settings: DutyBudgetSettings) -> Result<(), Error> {
+        check_budget(&settings)?;
+
+        self.settings = settings;
+        self.level = 0;
+
+        Ok(())
+    }
+
+    pub fn settings(&self) -> &DutyBudgetSettings {
+        &self.settings
+    }
+
+    /// Sends `cmd` to the motor, capping its throttle while derated.
+    pub fn set(&mut self, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.account(now_ms);
+        self.command = Some(cmd);
+        self.apply()
+    }
+
+    /// Accounts for the time since the last call, derating or restoring the throttle as the
+    /// budget runs out or recovers. Must be called regularly, at least as often as the
+    /// precision of the budget needs.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        if self.account(now_ms) {
+            self.apply()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the budget is spent and the throttle capped
+    pub fn is_derated(&self) -> bool {
+        self.derated
+    }
+
+    /// Time left above the threshold before derating, in milliseconds
+    pub fn remaining_ms(&self) -> u32 {
+        let off_ms = u64::from(self.settings.window_ms - self.settings.budget_ms);
+        (u64::from(self.settings.budget_ms) - self.level / off_ms) as u32
+    }
+
+    /// Number of times the budget has been spent, saturating at `u32::MAX`
+    pub fn trip_count(&self) -> u32 {
+        self.trip_count
+    }
+
+    pub fn reset_trip_count(&mut self) {
+        self.trip_count = 0;
+    }
+
+    pub fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+
+    /// Fills or drains the bucket for the time since the last call, at the throttle applied
+    /// meanwhile. Returns whether the derating changed.
+    fn account(&mut self, now_ms: u32) -> bool {
+        let Some(last_ms) = self.last_ms.replace(now_ms) else {
+            return false;
+        };
+
+        let elapsed = u64::from(now_ms.wrapping_sub(last_ms));
+        let budget_ms = u64::from(self.settings.budget_ms);
+        let off_ms = u64::from(self.settings.window_ms - self.settings.budget_ms);
+        let capacity = budget_ms * off_ms;
+
+        self.level = if self.motor.get_throttle() > self.settings.threshold {
+            (self.level + elapsed * off_ms).min(capacity)
+        } else {
+            self.level.saturating_sub(elapsed * budget_ms)
+        };
+
+        if !self.derated && self.level >= capacity {
+            self.derated = true;
+            self.trip_count = self.trip_count.saturating_add(1);
+            return true;
+        }
+
+        if self.derated && self.level == 0 {
+            self.derated = false;
+            return true;
+        }
+
+        false
+    }
+
+    fn apply(&mut self) -> Result<(), Error> {
+        match self.command {
+            Some(Command::Drive { direction, throttle }) if self.derated => {
+                self.motor.set(Command::Drive { direction, throttle: throttle.min(self.settings.derated_throttle) })
+            }
+            Some(cmd) => self.motor.set(cmd),
+            None => Ok(()),
+        }
+    }
+}
+
+fn check_budget(settings: &DutyBudgetSettings) -> Result<(), Error> {
+    if settings.budget_ms != 0 && settings.window_ms != 0 && settings.budget_ms < settings.window_ms {
+        Ok(())
+    } else {
+        Err(Error::ConfigInvalid)
+    }
+}
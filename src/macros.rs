@@ -0,0 +1,33 @@
+/// Builds an [`L298NHBridge`](crate::L298NHBridge) from two unconfigured GPIO pins and a
+/// PWM output, converting the pins with the HAL's `into_push_pull_output()`.
+///
+/// The PWM output must already be configured and enabled, e.g.
+/// `l298n!(gpiob.pb3, gpiob.pb4, left_enable)?`. Returns the result of
+/// [`L298NHBridge::new`](crate::L298NHBridge::new).
+#[macro_export]
+macro_rules! l298n {
+    ($dir1:expr, $dir2:expr, $enable:expr $(,)?) => {
+        $crate::L298NHBridge::new($dir1.into_push_pull_output(), $dir2.into_push_pull_output(), $enable)
+    };
+}
+
+/// Builds a [`DifferentialDrive`](crate::DifferentialDrive) from a wiring table of the
+/// direction pins and PWM output of each side, see [`l298n!`]. Returns a `Result`, failing
+/// if either bridge fails to build:
+///
+/// `differential_drive! { left: (gpiob.pb3, gpiob.pb4, left_enable), right: (gpioa.pa8, gpiob.pb15, right_enable) }?`
+#[macro_export]
+macro_rules! differential_drive {
+    (
+        left: ($left_dir1:expr, $left_dir2:expr, $left_enable:expr $(,)?),
+        right: ($right_dir1:expr, $right_dir2:expr, $right_enable:expr $(,)?) $(,)?
+    ) => {
+        match (
+            $crate::l298n!($left_dir1, $left_dir2, $left_enable),
+            $crate::l298n!($right_dir1, $right_dir2, $right_enable),
+        ) {
+            (Ok(left), Ok(right)) => Ok($crate::DifferentialDrive::new(left, right)),
+            (Err(error), _) | (_, Err(error)) => Err(error),
+        }
+    };
+}
@@ -0,0 +1,395 @@
+use crate::{HBridgeDriver, EncoderReader, DifferentialDrive, StopMode, FULL_SCALE, GAIN_FRAC_BITS, Error};
+use crate::mixer::desaturate;
+
+/// Wheel and encoder geometry of a [`DifferentialDrive`] robot
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DriveGeometry {
+    /// Distance between the centers of the left and right wheels
+    pub track_width_mm: u32,
+    pub wheel_diameter_mm: u32,
+    /// Encoder counts per wheel revolution, including any gearing
+    pub counts_per_rev: u32,
+}
+
+impl DriveGeometry {
+
+    /// Encoder counts for a straight distance of `mm`
+    pub fn counts_for_distance(&self, mm: i32) -> i64 {
+        // pi ~= 355 / 113, within 0.3 ppm
+        let counts = i128::from(mm) * i128::from(self.counts_per_rev) * 113;
+        (counts / (355 * i128::from(self.wheel_diameter_mm.max(1)))) as i64
+    }
+
+    /// Left and right wheel speeds in mm/s for `twist`
+    pub fn wheel_speeds(&self, twist: Twist) -> (i32, i32) {
+        // mrad/s * mm / 2000 is the speed of each wheel around the center, in mm/s
+        let turn = i64::from(twist.angular_mrad_per_sec) * i64::from(self.track_width_mm) / 2000;
+        let linear = i64::from(twist.linear_mm_per_sec);
+
+        let clamp = |speed: i64| speed.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        (clamp(linear - turn), clamp(linear + turn))
+    }
+
+    /// Left and right wheel speeds in encoder counts/s for `twist`, e.g. as setpoints for a
+    /// [`VelocityController`](crate::VelocityController) on each side
+    pub fn wheel_speeds_counts(&self, twist: Twist) -> (i32, i32) {
+        let (left, right) = self.wheel_speeds(twist);
+        let clamp = |counts: i64| counts.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+
+        (clamp(self.counts_for_distance(left)), clamp(self.counts_for_distance(right)))
+    }
+
+    /// Open-loop left and right signed throttles for `twist`, for a robot which reaches
+    /// `full_throttle_mm_per_sec` at full throttle. If a side would saturate, both are scaled
+    /// down together to keep the turn ratio.
+    pub fn wheel_throttles(&self, twist: Twist, full_throttle_mm_per_sec: u32) -> (i32, i32) {
+        let (left, right) = self.wheel_speeds(twist);
+        let full_speed = i64::from(full_throttle_mm_per_sec.max(1));
+
+        let scale = |speed: i32| {
+            let throttle = i64::from(speed) * i64::from(FULL_SCALE) / full_speed;
+            throttle.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+        };
+        desaturate(scale(left), scale(right))
+    }
+
+    /// Encoder counts for a wheel following a circle of `radius_mm` through `degrees`
+    fn counts_for_arc(&self, radius_mm: i64, degrees: i32) -> i64 {
+        // 2 pi r * deg / 360 over pi d counts per rev, pi cancels
+        let counts = i128::from(radius_mm) * i128::from(degrees) * i128::from(self.counts_per_rev);
+        (counts / (180 * i128::from(self.wheel_diameter_mm.max(1)))) as i64
+    }
+}
+
+/// A body velocity command following the ROS `Twist` convention: positive linear is forward
+/// and positive angular is anticlockwise (a left turn)
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Twist {
+    pub linear_mm_per_sec: i32,
+    pub angular_mrad_per_sec: i32,
+}
+
+/// Geometry of a car-like chassis with steered front wheels and two driven rear wheels,
+/// for an electronic differential: the rear wheels are driven at the speeds the turning
+/// circle set by the steering needs, rather than scrubbing against each other.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SteeringGeometry {
+    /// Distance between the front and rear axles
+    pub wheelbase_mm: u32,
+    /// Distance between the centers of the rear wheels
+    pub track_width_mm: u32,
+}
+
+impl SteeringGeometry {
+
+    /// Left and right rear wheel speeds relative to the center of the rear axle, in Q16
+    /// fixed point (`1 << 16` is 1.0), for a steering angle of `steering_mrad`. Positive
+    /// steering turns left, matching [`Twist`]. The angle is limited to ±80°.
+    ///
+    /// The inner wheel follows a circle of radius `R - track / 2` and the outer one
+    /// `R + track / 2`, where `R = wheelbase / tan(steering)`.
+    pub fn wheel_factors(&self, steering_mrad: i32) -> (i32, i32) {
+        let one = 1i64 << GAIN_FRAC_BITS;
+        let x = i64::from(steering_mrad.clamp(-MAX_STEERING_MRAD, MAX_STEERING_MRAD)) * one / 1000;
+
+        // tan(x) ~= x (15 - x²) / (15 - 6x²), within 0.2% up to 60°
+        let x2 = x * x / one;
+        let tan = x * (15 * one - x2) / (15 * one - 6 * x2);
+
+        // (track / 2) / R = track * tan / (2 * wheelbase)
+        let offset = i64::from(self.track_width_mm) * tan / (2 * i64::from(self.wheelbase_mm.max(1)));
+
+        let clamp = |factor: i64| factor.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        (clamp(one - offset), clamp(one + offset))
+    }
+
+    /// Left and right signed throttles for driving at `throttle` (at the center of the rear
+    /// axle) with a steering angle of `steering_mrad`, e.g. for
+    /// [`DifferentialDrive::tank`]. If the outer wheel would saturate, both are scaled down
+    /// together to keep the ratio.
+    pub fn wheel_throttles(&self, throttle: i32, steering_mrad: i32) -> (i32, i32) {
+        let (left, right) = self.wheel_factors(steering_mrad);
+        let throttle = i64::from(throttle.clamp(-FULL_SCALE, FULL_SCALE));
+
+        let scale = |factor: i32| {
+            let scaled = (throttle * i64::from(factor)) >> GAIN_FRAC_BITS;
+            scaled.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+        };
+        desaturate(scale(left), scale(right))
+    }
+}
+
+/// Steering angles beyond this have the tangent running away
+const MAX_STEERING_MRAD: i32 = 1396;
+
+/// Target encoder counts for each side of a manoeuvre
+#[derive(Clone, Copy)]
+struct Manoeuvre {
+    start: (i64, i64),
+    distance: (i64, i64),
+}
+
+/// Encoder-based distance control for a [`DifferentialDrive`], with straight, turn-in-place
+/// and arc manoeuvres.
+///
+/// Manoeuvres are non-blocking: start one, then call [`DistanceDrive::update`] periodically
+/// until it reports completion. The side with further to go is driven proportionally to its
+/// remaining distance, and the other side follows in proportion so arcs keep their shape.
+///
+/// Positive distances and angles are forward and clockwise. The encoders must count up when
+/// driving forward.
+pub struct DistanceDrive<L, R, EL, ER>
+where
+    L: HBridgeDriver,
+    R: HBridgeDriver,
+    EL: EncoderReader,
+    ER: EncoderReader,
+{
+    drive: DifferentialDrive<L, R>,
+    left_encoder: EL,
+    right_encoder: ER,
+    geometry: DriveGeometry,
+    kp: i32,
+    max_throttle: u16,
+    tolerance: u32,
+    manoeuvre: Option<Manoeuvre>,
+}
+
+impl<L, R, EL, ER> DistanceDrive<L, R, EL, ER>
+where
+    L: HBridgeDriver,
+    R: HBridgeDriver,
+    EL: EncoderReader,
+    ER: EncoderReader,
+{
+
+    /// Wraps `drive` and its encoders. The default gain reaches full throttle 1024 counts
+    /// from the target, within a tolerance of 2 counts.
+    pub fn new(drive: DifferentialDrive<L, R>, left_encoder: EL, right_encoder: ER, geometry: DriveGeometry) -> Self {
+        Self {
+            drive,
+            left_encoder,
+            right_encoder,
+            geometry,
+            kp: 64 << GAIN_FRAC_BITS,
+            max_throttle: u16::MAX,
+            tolerance: 2,
+            manoeuvre: None,
+        }
+    }
+
+    /// Sets the gain from remaining encoder counts to throttle, Q16
+    pub fn set_gain(&mut self, kp: i32) {
+        self.kp = kp;
+    }
+
+    pub fn set_max_throttle(&mut self, max_throttle: u16) {
+        self.max_throttle = max_throttle;
+    }
+
+    /// A manoeuvre is complete once both sides are within `counts` of their targets
+    pub fn set_tolerance(&mut self, counts: u32) {
+        self.tolerance = counts;
+    }
+
+    pub fn geometry(&self) -> DriveGeometry {
+        self.geometry
+    }
+
+    /// Starts driving `mm` straight ahead, negative is backwards
+    pub fn drive_distance(&mut self, mm: i32) {
+        let counts = self.geometry.counts_for_distance(mm);
+        self.start(counts, counts);
+    }
+
+    /// Starts rotating `degrees` on the spot, positive is clockwise
+    pub fn turn_in_place(&mut self, degrees: i32) {
+        let half_track = i64::from(self.geometry.track_width_mm) / 2;
+        let counts = self.geometry.counts_for_arc(half_track, degrees);
+        self.start(counts, -counts);
+    }
+
+    /// Starts driving `degrees` around a circle of `radius_mm`, measured to the center of the
+    /// robot. A positive radius curves to the right, negative to the left, and negative
+    /// `degrees` drive the arc backwards.
+    pub fn drive_arc(&mut self, radius_mm: i32, degrees: i32) {
+        let half_track = i64::from(self.geometry.track_width_mm) / 2;
+        let radius = i64::from(radius_mm);
+        let degrees = if radius_mm < 0 { -degrees } else { degrees };
+
+        let left = self.geometry.counts_for_arc(radius + half_track, degrees);
+        let right = self.geometry.counts_for_arc(radius - half_track, degrees);
+        self.start(left, right);
+    }
+
+    /// Whether a manoeuvre is in progress
+    pub fn is_busy(&self) -> bool {
+        self.manoeuvre.is_some()
+    }
+
+    /// Abandons the current manoeuvre and stops using `stop_mode`
+    pub fn cancel(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.manoeuvre = None;
+        self.drive.stop(stop_mode)
+    }
+
+    /// Same as [`DistanceDrive::cancel`], for a common stop surface across the aggregates
+    pub fn stop_all(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.cancel(stop_mode)
+    }
+
+    /// Abandons the current manoeuvre and brakes both sides at once
+    pub fn estop_all(&mut self) -> Result<(), Error> {
+        self.cancel(StopMode::Brake)
+    }
+
+    /// Reads the encoders and drives towards the current manoeuvre's target. Returns
+    /// `Ok(true)` on the update the manoeuvre completes, after braking.
+    pub fn update(&mut self) -> Result<bool, Error> {
+        let Some(manoeuvre) = self.manoeuvre else {
+            return Ok(false);
+        };
+
+        let travelled = (
+            self.left_encoder.count() - manoeuvre.start.0,
+            self.right_encoder.count() - manoeuvre.start.1,
+        );
+        let remaining = (manoeuvre.distance.0 - travelled.0, manoeuvre.distance.1 - travelled.1);
+
+        let tolerance = i64::from(self.tolerance);
+        if remaining.0.abs() <= tolerance && remaining.1.abs() <= tolerance {
+            self.manoeuvre = None;
+            self.drive.stop(StopMode::Brake)?;
+            return Ok(true);
+        }
+
+        // Lead with the side which has further to go, the other side follows at the same
+        // fraction of its own distance
+        let (left, right) = if manoeuvre.distance.0.abs() >= manoeuvre.distance.1.abs() {
+            self.follow(manoeuvre.distance.0, remaining.0, manoeuvre.distance.1, travelled.0, travelled.1)
+        } else {
+            let (lead, follow) = self.follow(manoeuvre.distance.1, remaining.1, manoeuvre.distance.0, travelled.1, travelled.0);
+            (follow, lead)
+        };
+
+        self.drive.tank(left, right)?;
+        Ok(false)
+    }
+
+    pub fn drive(&mut self) -> &mut DifferentialDrive<L, R> {
+        &mut self.drive
+    }
+
+    /// Releases the drive and the left and right encoders.
+    pub fn free(self) -> (DifferentialDrive<L, R>, EL, ER) {
+        (self.drive, self.left_encoder, self.right_encoder)
+    }
+
+    fn start(&mut self, left: i64, right: i64) {
+        self.manoeuvre = Some(Manoeuvre {
+            start: (self.left_encoder.count(), self.right_encoder.count()),
+            distance: (left, right),
+        });
+    }
+
+    /// Throttles for the leading and following sides
+    fn follow(&self, lead_distance: i64, lead_remaining: i64, follow_distance: i64, lead_travelled: i64, follow_travelled: i64) -> (i32, i32) {
+        let max = i128::from(self.max_throttle);
+        let kp = i128::from(self.kp);
+
+        let lead = ((kp * i128::from(lead_remaining)) >> GAIN_FRAC_BITS).clamp(-max, max);
+
+        // Where the following side should be given the lead's progress, and its throttle
+        // scaled by the ratio of distances and corrected towards that position
+        let lead_distance = i128::from(lead_distance);
+        let (follow_expected, follow_feed) = if lead_distance == 0 {
+            (0, 0)
+        } else {
+            (
+                i128::from(lead_travelled) * i128::from(follow_distance) / lead_distance,
+                lead * i128::from(follow_distance) / lead_distance,
+            )
+        };
+        let follow_correction = (kp * (follow_expected - i128::from(follow_travelled))) >> GAIN_FRAC_BITS;
+        let follow = (follow_feed + follow_correction).clamp(-max, max);
+
+        (lead as i32, follow as i32)
+    }
+}
+
+/// One leg of a path run by a [`PathFollower`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// See [`DistanceDrive::drive_distance`]
+    Straight { mm: i32 },
+    /// See [`DistanceDrive::drive_arc`]
+    Arc { radius_mm: i32, degrees: i32 },
+    /// See [`DistanceDrive::turn_in_place`]
+    Turn { degrees: i32 },
+}
+
+/// Runs a list of [`Segment`]s one after another on a [`DistanceDrive`].
+///
+/// The segments are borrowed, so they can live in an array, a `heapless::Vec` or any other
+/// slice.
+pub struct PathFollower<'a> {
+    segments: &'a [Segment],
+    next: usize,
+    running: bool,
+}
+
+impl<'a> PathFollower<'a> {
+
+    pub fn new(segments: &'a [Segment]) -> Self {
+        Self { segments, next: 0, running: false }
+    }
+
+    /// Advances the path: starts the next segment once the drive is idle and updates the
+    /// running one. `on_complete` is called with the index of each segment as it completes.
+    /// Returns `Ok(true)` once every segment has completed.
+    pub fn update<L, R, EL, ER, F>(&mut self, drive: &mut DistanceDrive<L, R, EL, ER>, mut on_complete: F) -> Result<bool, Error>
+    where
+        L: HBridgeDriver,
+        R: HBridgeDriver,
+        EL: EncoderReader,
+        ER: EncoderReader,
+        F: FnMut(usize, &Segment),
+    {
+        if !self.running {
+            let Some(segment) = self.segments.get(self.next) else {
+                return Ok(true);
+            };
+
+            match *segment {
+                Segment::Straight { mm } => drive.drive_distance(mm),
+                Segment::Arc { radius_mm, degrees } => drive.drive_arc(radius_mm, degrees),
+                Segment::Turn { degrees } => drive.turn_in_place(degrees),
+            }
+            self.running = true;
+        }
+
+        if drive.update()? {
+            on_complete(self.next, &self.segments[self.next]);
+            self.next += 1;
+            self.running = false;
+        }
+
+        Ok(self.is_finished())
+    }
+
+    /// The index of the segment being run, or of the next one to run
+    pub fn current_index(&self) -> usize {
+        self.next
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.segments.len()
+    }
+
+    /// Starts the path again from the first segment. A running manoeuvre should be
+    /// cancelled on the drive first.
+    pub fn restart(&mut self) {
+        self.next = 0;
+        self.running = false;
+    }
+}
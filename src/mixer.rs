@@ -0,0 +1,581 @@
+use crate::{HBridgeDriver, EncoderReader, MotorGroup, Command, Direction, StopMode, GAIN_FRAC_BITS, Error};
+use crate::pid::slew_toward;
+
+/// Full scale of a signed throttle or joystick axis, `-FULL_SCALE..=FULL_SCALE`
+pub const FULL_SCALE: i32 = u16::MAX as i32;
+
+/// Conditions one raw joystick axis before mixing: a center deadband, an expo curve and a
+/// slew limit, all in integer math.
+///
+/// Values are signed full scale, `-FULL_SCALE..=FULL_SCALE`.
+#[derive(Clone, Copy)]
+pub struct AxisConditioner {
+    deadband: u16,
+    expo: u16,
+    max_step: Option<u32>,
+    last: i32,
+}
+
+impl AxisConditioner {
+
+    /// A conditioner which passes the axis through unchanged
+    pub const fn new() -> Self {
+        Self { deadband: 0, expo: 0, max_step: None, last: 0 }
+    }
+
+    /// Inputs within `deadband` of center read as zero. The remaining travel is rescaled so
+    /// the output still starts at zero and reaches full scale.
+    pub fn set_deadband(&mut self, deadband: u16) {
+        self.deadband = deadband;
+    }
+
+    /// Blends a cubic curve into the response, giving finer control around center. `0` is
+    /// linear, `u16::MAX` is fully cubic.
+    pub fn set_expo(&mut self, expo: u16) {
+        self.expo = expo;
+    }
+
+    /// Limits how far the output may move per call. `None` disables the limit.
+    pub fn set_slew(&mut self, max_step: Option<u32>) {
+        self.max_step = max_step;
+    }
+
+    /// Conditions `raw`, which is clamped to full scale first
+    pub fn apply(&mut self, raw: i32) -> i32 {
+        let raw = i64::from(raw.clamp(-FULL_SCALE, FULL_SCALE));
+        let full_scale = i64::from(FULL_SCALE);
+        let deadband = i64::from(self.deadband);
+
+        let shaped = if raw.abs() <= deadband {
+            0
+        } else {
+            // The deadband is at most full scale and excluded by the branch above, so the
+            // divisor is non-zero
+            let x = raw.signum() * (raw.abs() - deadband) * full_scale / (full_scale - deadband);
+
+            // x * ((1 - e) + e * x^2), with x and e normalized to full scale. |x^3| is below
+            // 2^48, well within i64.
+            let expo = i64::from(self.expo);
+            let cubic = x * x * x / (full_scale * full_scale);
+            (x * (full_scale - expo) + cubic * expo) / full_scale
+        };
+
+        self.last = match self.max_step {
+            Some(max_step) => slew_toward(self.last, shaped as i32, max_step),
+            None => shaped as i32,
+        };
+
+        self.last
+    }
+
+    /// Forgets the slew limiter's state so the next output starts from zero
+    pub fn reset(&mut self) {
+        self.last = 0;
+    }
+}
+
+impl Default for AxisConditioner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps raw ADC readings from a joystick axis or potentiometer to a signed full scale value
+/// for the mixer, `-FULL_SCALE..=FULL_SCALE`.
+///
+/// Readings between `center` and `max` map to `0..=FULL_SCALE`, and between `min` and `center`
+/// to `-FULL_SCALE..=0`. For a unipolar speed knob set `center` equal to `min`.
+#[derive(Clone, Copy)]
+pub struct AnalogAxis {
+    min: u16,
+    center: u16,
+    max: u16,
+    deadband: u16,
+    inverted: bool,
+}
+
+impl AnalogAxis {
+
+    /// An axis calibrated to `min`, `center` and `max` raw readings, with no deadband.
+    /// `center` is clamped into `min..=max`.
+    pub fn new(min: u16, center: u16, max: u16) -> Self {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        Self { min, center: center.clamp(min, max), max, deadband: 0, inverted: false }
+    }
+
+    /// Recalibrates the center, e.g. from a reading taken at startup with the stick released
+    pub fn set_center(&mut self, center: u16) {
+        self.center = center.clamp(self.min, self.max);
+    }
+
+    /// Readings within `deadband` raw counts of center map to zero
+    pub fn set_deadband(&mut self, deadband: u16) {
+        self.deadband = deadband;
+    }
+
+    /// Inverts the output, for axes wired or mounted the other way round
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    /// Maps a raw reading, which is clamped to the calibrated range first
+    pub fn map(&self, raw: u16) -> i32 {
+        let raw = i64::from(raw.clamp(self.min, self.max));
+        let center = i64::from(self.center);
+        let deadband = i64::from(self.deadband);
+
+        // Offset from the edge of the deadband, over the travel remaining beyond it
+        let (offset, travel) = if raw > center + deadband {
+            (raw - center - deadband, i64::from(self.max) - center - deadband)
+        } else if raw < center - deadband {
+            (raw - center + deadband, center - deadband - i64::from(self.min))
+        } else {
+            return 0;
+        };
+
+        // The branches above are only taken with travel remaining, so it is non-zero
+        let value = (offset * i64::from(FULL_SCALE) / travel) as i32;
+        if self.inverted { -value } else { value }
+    }
+}
+
+/// Rate limits for one side of a [`DifferentialDrive`], in throttle per call.
+///
+/// `accel` applies while the throttle moves away from zero and `decel` while it moves
+/// towards zero. A reversal first decelerates to zero.
+#[derive(Clone, Copy)]
+pub struct SlewLimit {
+    pub accel: u32,
+    pub decel: u32,
+}
+
+impl SlewLimit {
+    pub const NONE: Self = Self { accel: u32::MAX, decel: u32::MAX };
+
+    /// Steps `current` towards `target` within the limits
+    pub fn step(&self, current: i32, target: i32) -> i32 {
+        let accelerating = (current >= 0 && target > current) || (current <= 0 && target < current);
+
+        if accelerating {
+            slew_toward(current, target, self.accel)
+        } else {
+            let target = if current != 0 && target.signum() == -current.signum() { 0 } else { target };
+            slew_toward(current, target, self.decel)
+        }
+    }
+}
+
+/// Heading hold settings for [`DifferentialDrive::arcade_heading_hold`].
+///
+/// Headings are in any unit the gyro reports, increasing clockwise.
+#[derive(Clone, Copy)]
+pub struct HeadingHold {
+    /// Proportional gain from heading error to turn, Q16
+    pub kp: i32,
+    /// The largest turn correction applied
+    pub max_correction: u32,
+    /// The heading of a full turn, e.g. 3600 for decidegrees, so errors are taken the short
+    /// way round. `0` if the heading does not wrap.
+    pub full_turn: u32,
+}
+
+impl HeadingHold {
+
+    /// The turn correction steering `heading` back to `target`
+    fn correction(&self, target: i32, heading: i32) -> i32 {
+        let mut error = i64::from(target) - i64::from(heading);
+
+        if self.full_turn != 0 {
+            let full_turn = i64::from(self.full_turn);
+            error = error.rem_euclid(full_turn);
+            if error > full_turn / 2 {
+                error -= full_turn;
+            }
+        }
+
+        let correction = (i64::from(self.kp) * error) >> GAIN_FRAC_BITS;
+        let limit = i64::from(self.max_correction.min(FULL_SCALE as u32));
+        correction.clamp(-limit, limit) as i32
+    }
+}
+
+/// Cross-coupled drive-straight settings for [`DifferentialDrive::arcade_straight`]
+#[derive(Clone, Copy)]
+pub struct StraightCorrection {
+    /// Proportional gain from the difference in distance travelled, in encoder counts, to
+    /// throttle, Q16
+    pub kp: i32,
+    /// The largest throttle trimmed off the faster side
+    pub max_trim: u32,
+}
+
+/// Traction control for a two-motor robot with an encoder on each side, cutting the throttle
+/// of a wheel that spins up implausibly faster than the other, e.g. under full throttle on
+/// a smooth floor.
+///
+/// Each update compares how much each side's speed grew. A side gaining more than the
+/// threshold over the other is taken to be slipping and has its throttle cut, by a
+/// further `cut_step` each update it keeps slipping. Once it grips again the cut recovers
+/// by `recovery_step` each update.
+pub struct TractionControl {
+    slip_threshold: u32,
+    cut_step: u32,
+    recovery_step: u32,
+    last_speeds: Option<(i32, i32)>,
+    left_cut: u32,
+    right_cut: u32,
+}
+
+impl TractionControl {
+
+    /// `slip_threshold` is in counts/s per update, the cut and recovery in throttle per
+    /// update
+    pub fn new(slip_threshold: u32, cut_step: u32, recovery_step: u32) -> Self {
+        Self { slip_threshold, cut_step, recovery_step, last_speeds: None, left_cut: 0, right_cut: 0 }
+    }
+
+    /// Limits `left` and `right` signed throttles given the latest measured speeds of each
+    /// side in counts/s, e.g. from a [`VelocityEstimator`] each. Must be called at a fixed
+    /// rate, passing the result to e.g. [`DifferentialDrive::tank`].
+    ///
+    /// [`VelocityEstimator`]: crate::VelocityEstimator
+    pub fn apply(&mut self, left: i32, right: i32, left_speed: i32, right_speed: i32) -> (i32, i32) {
+        let (last_left, last_right) = self.last_speeds.unwrap_or((left_speed, right_speed));
+        self.last_speeds = Some((left_speed, right_speed));
+
+        let gain = |speed: i32, last: i32| i64::from(speed.unsigned_abs()) - i64::from(last.unsigned_abs());
+        let difference = gain(left_speed, last_left) - gain(right_speed, last_right);
+        let threshold = i64::from(self.slip_threshold);
+
+        let step = |cut: u32, slipping: bool| if slipping {
+            cut.saturating_add(self.cut_step).min(FULL_SCALE as u32)
+        } else {
+            cut.saturating_sub(self.recovery_step)
+        };
+        self.left_cut = step(self.left_cut, difference > threshold);
+        self.right_cut = step(self.right_cut, -difference > threshold);
+
+        (trim_toward_zero(left, self.left_cut), trim_toward_zero(right, self.right_cut))
+    }
+
+    /// The throttle currently cut from the left and right sides
+    pub fn get_cuts(&self) -> (u32, u32) {
+        (self.left_cut, self.right_cut)
+    }
+
+    /// Whether either side currently has its throttle cut
+    pub fn is_active(&self) -> bool {
+        self.left_cut != 0 || self.right_cut != 0
+    }
+
+    /// Forgets the last speeds and restores full throttle, e.g. after stopping
+    pub fn reset(&mut self) {
+        self.last_speeds = None;
+        self.left_cut = 0;
+        self.right_cut = 0;
+    }
+}
+
+/// Differential (skid) steering for a two-motor robot.
+///
+/// Throttle and turn inputs are signed full scale, positive is forward and clockwise.
+/// Both are passed through an [`AxisConditioner`] before mixing, and each side's output can
+/// be rate limited with a [`SlewLimit`].
+///
+/// Both sides are always commanded, even if the left fails, returning the first error.
+pub struct DifferentialDrive<L, R>
+where
+    L: HBridgeDriver,
+    R: HBridgeDriver,
+{
+    left: L,
+    right: R,
+    throttle_axis: AxisConditioner,
+    turn_axis: AxisConditioner,
+    left_slew: SlewLimit,
+    right_slew: SlewLimit,
+    left_output: i32,
+    right_output: i32,
+    heading_hold: Option<HeadingHold>,
+    held_heading: Option<i32>,
+    straight_correction: Option<StraightCorrection>,
+    /// Encoder counts of both sides when straight driving began
+    straight_start: Option<(i64, i64)>,
+}
+
+impl<L, R> DifferentialDrive<L, R>
+where
+    L: HBridgeDriver,
+    R: HBridgeDriver,
+{
+
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left,
+            right,
+            throttle_axis: AxisConditioner::new(),
+            turn_axis: AxisConditioner::new(),
+            left_slew: SlewLimit::NONE,
+            right_slew: SlewLimit::NONE,
+            left_output: 0,
+            right_output: 0,
+            heading_hold: None,
+            held_heading: None,
+            straight_correction: None,
+            straight_start: None,
+        }
+    }
+
+    pub fn throttle_axis(&mut self) -> &mut AxisConditioner {
+        &mut self.throttle_axis
+    }
+
+    pub fn turn_axis(&mut self) -> &mut AxisConditioner {
+        &mut self.turn_axis
+    }
+
+    /// Limits how fast each side's output may change, so aggressive turns don't slam a side
+    /// from forward to reverse
+    pub fn set_slew_limits(&mut self, left: SlewLimit, right: SlewLimit) {
+        self.left_slew = left;
+        self.right_slew = right;
+    }
+
+    /// The signed throttles last applied to the left and right sides
+    pub fn get_outputs(&self) -> (i32, i32) {
+        (self.left_output, self.right_output)
+    }
+
+    /// Enables heading hold for [`DifferentialDrive::arcade_heading_hold`], `None` disables it
+    pub fn set_heading_hold(&mut self, hold: Option<HeadingHold>) {
+        self.heading_hold = hold;
+        self.held_heading = None;
+    }
+
+    /// Enables encoder drive-straight correction for [`DifferentialDrive::arcade_straight`],
+    /// `None` disables it
+    pub fn set_straight_correction(&mut self, correction: Option<StraightCorrection>) {
+        self.straight_correction = correction;
+        self.straight_start = None;
+    }
+
+    /// Arcade mixing: `throttle` sets the speed and `turn` the difference between the sides.
+    /// If a side would saturate, both are scaled down together to keep the turn ratio.
+    pub fn arcade(&mut self, throttle: i32, turn: i32) -> Result<(), Error> {
+        let throttle = self.throttle_axis.apply(throttle);
+        let turn = self.turn_axis.apply(turn);
+
+        let (left, right) = desaturate(throttle + turn, throttle - turn);
+        self.tank(left, right)
+    }
+
+    /// Arcade mixing which keeps the robot driving straight while the turn input is centered.
+    ///
+    /// `read_heading` is called every update. The heading is captured when the turn input is
+    /// released and deviations from it are corrected proportionally. While turning or
+    /// stopped, or when heading hold is disabled, this is plain [`DifferentialDrive::arcade`].
+    pub fn arcade_heading_hold<F>(&mut self, throttle: i32, turn: i32, mut read_heading: F) -> Result<(), Error>
+    where
+        F: FnMut() -> i32,
+    {
+        let throttle = self.throttle_axis.apply(throttle);
+        let mut turn = self.turn_axis.apply(turn);
+
+        match self.heading_hold {
+            Some(hold) if turn == 0 && throttle != 0 => {
+                let heading = read_heading();
+                let target = *self.held_heading.get_or_insert(heading);
+                turn = hold.correction(target, heading);
+            },
+            _ => self.held_heading = None,
+        }
+
+        let (left, right) = desaturate(throttle + turn, throttle - turn);
+        self.tank(left, right)
+    }
+
+    /// Arcade mixing which keeps the robot driving straight using an encoder on each side,
+    /// without a gyro.
+    ///
+    /// While the turn input is centered, the side which has travelled further since the turn
+    /// was released is trimmed proportionally, matching the two sides' tick rates. While
+    /// turning or stopped, or when the correction is disabled, this is plain
+    /// [`DifferentialDrive::arcade`]. The encoders must count up when driving forward.
+    pub fn arcade_straight<EL, ER>(&mut self, throttle: i32, turn: i32, left: &mut EL, right: &mut ER) -> Result<(), Error>
+    where
+        EL: EncoderReader,
+        ER: EncoderReader,
+    {
+        let throttle = self.throttle_axis.apply(throttle);
+        let turn = self.turn_axis.apply(turn);
+
+        let (mut left_out, mut right_out) = desaturate(throttle + turn, throttle - turn);
+
+        match self.straight_correction {
+            Some(correction) if turn == 0 && throttle != 0 => {
+                let (left_count, right_count) = (left.count(), right.count());
+                let (left_start, right_start) = *self.straight_start.get_or_insert((left_count, right_count));
+
+                // Positive when the left side is ahead
+                let lead = (left_count - left_start).abs() - (right_count - right_start).abs();
+                let trim = (i128::from(correction.kp) * i128::from(lead).abs()) >> GAIN_FRAC_BITS;
+                let trim = trim.min(i128::from(correction.max_trim)) as u32;
+
+                if lead > 0 {
+                    left_out = trim_toward_zero(left_out, trim);
+                } else {
+                    right_out = trim_toward_zero(right_out, trim);
+                }
+            },
+            _ => self.straight_start = None,
+        }
+
+        self.tank(left_out, right_out)
+    }
+
+    /// Curvature ("cheesy") drive: `turn` sets the curvature of the path rather than the
+    /// speed difference, so the turning radius stays the same as the throttle changes and
+    /// the robot does not turn when stopped. With `quick_turn` set the turn input is mixed
+    /// as in [`DifferentialDrive::arcade`], allowing rotation in place.
+    pub fn curvature(&mut self, throttle: i32, turn: i32, quick_turn: bool) -> Result<(), Error> {
+        let throttle = self.throttle_axis.apply(throttle);
+        let turn = self.turn_axis.apply(turn);
+
+        let turn = if quick_turn {
+            turn
+        } else {
+            // |throttle * turn| / FULL_SCALE is at most FULL_SCALE
+            (i64::from(throttle).abs() * i64::from(turn) / i64::from(FULL_SCALE)) as i32
+        };
+
+        let (left, right) = desaturate(throttle + turn, throttle - turn);
+        self.tank(left, right)
+    }
+
+    /// Drives straight forward at `throttle`, subject to the slew limits
+    pub fn forward(&mut self, throttle: u16) -> Result<(), Error> {
+        let throttle = i32::from(throttle);
+        self.tank(throttle, throttle)
+    }
+
+    /// Drives straight backwards at `throttle`, subject to the slew limits
+    pub fn reverse(&mut self, throttle: u16) -> Result<(), Error> {
+        let throttle = i32::from(throttle);
+        self.tank(-throttle, -throttle)
+    }
+
+    /// Spins anticlockwise on the spot, the sides driven in opposite directions
+    pub fn spin_left(&mut self, throttle: u16) -> Result<(), Error> {
+        let throttle = i32::from(throttle);
+        self.tank(-throttle, throttle)
+    }
+
+    /// Spins clockwise on the spot, the sides driven in opposite directions
+    pub fn spin_right(&mut self, throttle: u16) -> Result<(), Error> {
+        let throttle = i32::from(throttle);
+        self.tank(throttle, -throttle)
+    }
+
+    /// Turns anticlockwise about the left wheel, which is held at zero throttle
+    pub fn pivot_left(&mut self, throttle: u16) -> Result<(), Error> {
+        self.tank(0, i32::from(throttle))
+    }
+
+    /// Turns clockwise about the right wheel, which is held at zero throttle
+    pub fn pivot_right(&mut self, throttle: u16) -> Result<(), Error> {
+        self.tank(i32::from(throttle), 0)
+    }
+
+    /// Drives each side directly with a signed throttle, clamped to full scale and subject
+    /// to the slew limits
+    pub fn tank(&mut self, left: i32, right: i32) -> Result<(), Error> {
+        let left = left.clamp(-FULL_SCALE, FULL_SCALE);
+        let right = right.clamp(-FULL_SCALE, FULL_SCALE);
+
+        self.left_output = self.left_slew.step(self.left_output, left);
+        self.right_output = self.right_slew.step(self.right_output, right);
+
+        let left = self.left.set(signed_command(self.left_output));
+        left.and(self.right.set(signed_command(self.right_output)))
+    }
+
+    /// Stops both sides using `stop_mode` and resets the input conditioning
+    pub fn stop(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.throttle_axis.reset();
+        self.turn_axis.reset();
+        self.left_output = 0;
+        self.right_output = 0;
+        self.held_heading = None;
+        self.straight_start = None;
+
+        let left = self.left.set(Command::Stop(stop_mode));
+        left.and(self.right.set(Command::Stop(stop_mode)))
+    }
+
+    /// Same as [`DifferentialDrive::stop`], for a common stop surface across the aggregates
+    pub fn stop_all(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.stop(stop_mode)
+    }
+
+    /// Brakes both sides at once, bypassing the slew limits
+    pub fn estop_all(&mut self) -> Result<(), Error> {
+        self.stop(StopMode::Brake)
+    }
+
+    /// Releases the left and right motors.
+    pub fn free(self) -> (L, R) {
+        (self.left, self.right)
+    }
+}
+
+/// Scales `left` and `right` down together so neither exceeds full scale
+pub(crate) fn desaturate(left: i32, right: i32) -> (i32, i32) {
+    let peak = left.unsigned_abs().max(right.unsigned_abs());
+    if peak <= FULL_SCALE as u32 {
+        return (left, right);
+    }
+
+    let scale = |value: i32| (i64::from(value) * i64::from(FULL_SCALE) / i64::from(peak)) as i32;
+    (scale(left), scale(right))
+}
+
+/// Reduces the magnitude of `throttle` by `trim`, without changing its sign
+fn trim_toward_zero(throttle: i32, trim: u32) -> i32 {
+    throttle.signum() * throttle.unsigned_abs().saturating_sub(trim) as i32
+}
+
+/// The signed throttle of `cmd`, negative is reverse and stopping is zero
+pub(crate) fn signed_throttle(cmd: Command) -> i32 {
+    match cmd {
+        Command::Drive { direction: Direction::Forward, throttle } => i32::from(throttle),
+        Command::Drive { direction: Direction::Reverse, throttle } => -i32::from(throttle),
+        Command::Stop(_) => 0,
+    }
+}
+
+/// A drive command for a signed throttle, negative is reverse
+pub(crate) fn signed_command(throttle: i32) -> Command {
+    let direction = if throttle < 0 { Direction::Reverse } else { Direction::Forward };
+    let throttle = throttle.unsigned_abs().min(FULL_SCALE as u32) as u16;
+
+    Command::Drive { direction, throttle }
+}
+
+/// The left motor is index 0 and the right motor index 1. Commands bypass the input
+/// conditioning and slew limits.
+impl<L, R> MotorGroup for DifferentialDrive<L, R>
+where
+    L: HBridgeDriver,
+    R: HBridgeDriver,
+{
+    fn set_motor(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        let (motor, output): (&mut dyn HBridgeDriver, _) = match index {
+            0 => (&mut self.left, &mut self.left_output),
+            1 => (&mut self.right, &mut self.right_output),
+            _ => return Err(Error::ConfigInvalid),
+        };
+
+        *output = signed_throttle(cmd);
+        motor.set(cmd)
+    }
+}
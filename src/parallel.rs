@@ -0,0 +1,201 @@
+use embedded_hal::{digital, pwm};
+use core::convert::Infallible;
+
+use crate::{HBridgeDriver, BridgeInputs, Command, Direction, StopMode, Error, duty_from_fullscale};
+
+/// Driver for both halves of an **L298N** paralleled to drive a single motor at a higher
+/// current, as described in the datasheet.
+///
+/// ## Wiring
+/// The datasheet pairs Out1 with Out4 and Out2 with Out3, so the halves must be given
+/// such that `dir1_a`/`dir1_b` drive one pair of paralleled outputs and `dir2_a`/`dir2_b`
+/// the other:
+/// - `dir1_a`, `dir2_a`: In1, In2
+/// - `dir1_b`, `dir2_b`: In4, In3
+/// - `enable_a`, `enable_b`: EnA, EnB
+///
+/// The two halves always switch together. Whenever the direction inputs change, both
+/// enables are first pulled low so the halves are never driven against each other while
+/// the inputs are only partially updated.
+///
+/// Like [`L298NHBridge`], a brake holds both enables fully on while reporting a throttle of
+/// zero, and a stop keeps the last drive throttle.
+///
+/// [`L298NHBridge`]: crate::L298NHBridge
+pub struct L298NParallel<A1, A2, B1, B2, ENA, ENB>
+where 
+    A1: digital::OutputPin<Error = Infallible>,
+    A2: digital::OutputPin<Error = Infallible>,
+    B1: digital::OutputPin<Error = Infallible>,
+    B2: digital::OutputPin<Error = Infallible>,
+    ENA: pwm::SetDutyCycle<Error = Infallible>,
+    ENB: pwm::SetDutyCycle<Error = Infallible>,
+{
+    dir1_a: A1,
+    dir2_a: A2,
+    dir1_b: B1,
+    dir2_b: B2,
+    enable_a: ENA,
+    enable_b: ENB,
+    command: Command,
+    /// Throttle of the last drive command, kept while stopped
+    drive_throttle: u16,
+    inputs: Option<BridgeInputs>,
+}
+
+impl<A1, A2, B1, B2, ENA, ENB> L298NParallel<A1, A2, B1, B2, ENA, ENB>
+where 
+    A1: digital::OutputPin<Error = Infallible>,
+    A2: digital::OutputPin<Error = Infallible>,
+    B1: digital::OutputPin<Error = Infallible>,
+    B2: digital::OutputPin<Error = Infallible>,
+    ENA: pwm::SetDutyCycle<Error = Infallible>,
+    ENB: pwm::SetDutyCycle<Error = Infallible>,
+{
+
+    /// Takes the pins and puts both halves into the safe state, whatever the HAL left them
+    /// in: enables low first, then all direction inputs low.
+    pub fn new(
+        dir1_a: A1,
+        dir2_a: A2,
+        dir1_b: B1,
+        dir2_b: B2,
+        enable_a: ENA,
+        enable_b: ENB,
+    ) -> Result<Self, Error> {
+        let mut handle = Self {
+            dir1_a, dir2_a, dir1_b, dir2_b, enable_a, enable_b,
+            command: Command::Stop(StopMode::Coast),
+            drive_throttle: 0u16,
+            inputs: None,
+        };
+        handle.reinit()?;
+
+        Ok(handle)
+    }
+
+    pub fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd {
+            Command::Drive { direction, throttle } => {
+                match direction {
+                    Direction::Forward => self.set_inputs(BridgeInputs::Forward)?,
+                    Direction::Reverse => self.set_inputs(BridgeInputs::Reverse)?,
+                }
+
+                self.drive_throttle = throttle;
+                self.set_throttle(throttle)?;
+            },
+            Command::Stop(stop_mode) => {
+                match stop_mode {
+                    StopMode::Brake => {
+                        self.set_inputs(BridgeInputs::Brake)?;
+                        self.set_throttle(u16::MAX)?;
+                    },
+                    StopMode::Coast => self.set_throttle(0u16)?,
+                }
+            }
+        }
+        self.command = cmd;
+
+        Ok(())
+    }
+
+    /// The commanded drive throttle, zero while stopped
+    pub fn get_throttle(&self) -> u16 {
+        match self.command {
+            Command::Drive { throttle, .. } => throttle,
+            Command::Stop(_) => 0u16,
+        }
+    }
+
+    /// The throttle of the last drive command, which is kept while stopped
+    pub fn get_drive_throttle(&self) -> u16 {
+        self.drive_throttle
+    }
+
+    /// The motion last commanded
+    pub fn last_command(&self) -> Command {
+        self.command
+    }
+
+    /// The state both halves' direction inputs are set to, `None` if they haven't been
+    /// written since construction
+    pub fn get_inputs(&self) -> Option<BridgeInputs> {
+        self.inputs
+    }
+
+    /// Re-applies the safe state: both enables low, then all direction inputs low.
+    ///
+    /// The PWMs' max duty is read on every throttle change, so reconfigured timers are picked
+    /// up without further action.
+    pub fn reinit(&mut self) -> Result<(), Error> {
+        self.set_throttle(0u16)?;
+        self.inputs = None;
+        self.set_inputs(BridgeInputs::Off)?;
+        self.command = Command::Stop(StopMode::Coast);
+        self.drive_throttle = 0u16;
+
+        Ok(())
+    }
+
+    /// Releases the pins and PWMs, which are left as they are. Call
+    /// [`L298NParallel::reinit`] first to hand them over in the safe state.
+    pub fn free(self) -> (A1, A2, B1, B2, ENA, ENB) {
+        (self.dir1_a, self.dir2_a, self.dir1_b, self.dir2_b, self.enable_a, self.enable_b)
+    }
+
+    /// Sets both enables to the same throttle back to back, rounded like the single bridge
+    fn set_throttle(&mut self, throttle: u16) -> Result<(), Error> {
+        self.enable_a.set_duty_cycle(duty_from_fullscale(self.enable_a.max_duty_cycle(), throttle))?;
+        self.enable_b.set_duty_cycle(duty_from_fullscale(self.enable_b.max_duty_cycle(), throttle))?;
+
+        Ok(())
+    }
+
+    /// Applies the same direction inputs to both halves, disabling both halves first if
+    /// the inputs are about to change. The caller is responsible for re-applying a throttle.
+    fn set_inputs(&mut self, inputs: BridgeInputs) -> Result<(), Error> {
+        if self.inputs == Some(inputs) {
+            return Ok(());
+        }
+
+        let (dir1, dir2) = inputs.levels();
+
+        self.enable_a.set_duty_cycle(0u16)?;
+        self.enable_b.set_duty_cycle(0u16)?;
+
+        self.dir1_a.set_state(dir1.into())?;
+        self.dir1_b.set_state(dir1.into())?;
+        self.dir2_a.set_state(dir2.into())?;
+        self.dir2_b.set_state(dir2.into())?;
+        self.inputs = Some(inputs);
+
+        Ok(())
+    }
+}
+
+impl<A1, A2, B1, B2, ENA, ENB> HBridgeDriver for L298NParallel<A1, A2, B1, B2, ENA, ENB>
+where 
+    A1: digital::OutputPin<Error = Infallible>,
+    A2: digital::OutputPin<Error = Infallible>,
+    B1: digital::OutputPin<Error = Infallible>,
+    B2: digital::OutputPin<Error = Infallible>,
+    ENA: pwm::SetDutyCycle<Error = Infallible>,
+    ENB: pwm::SetDutyCycle<Error = Infallible>,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        L298NParallel::set(self, cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        L298NParallel::get_throttle(self)
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        L298NParallel::reinit(self)
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        Some(L298NParallel::last_command(self))
+    }
+}
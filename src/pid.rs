@@ -0,0 +1,204 @@
+/// Number of fractional bits in the fixed point PID gains
+pub const GAIN_FRAC_BITS: u32 = 16;
+
+/// A set of PID gains in Q16.16 fixed point, i.e. a gain of `1 << 16` is 1.0
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PidGains {
+    pub kp: i32,
+    pub ki: i32,
+    pub kd: i32,
+}
+
+/// How a [`Pid`] keeps its integral from winding up while the output is saturated
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AntiWindup {
+    /// The integral term is clamped to the output limits
+    Clamping,
+    /// The saturation error (saturated minus unsaturated output) is fed back into the
+    /// integral with this tracking gain in Q16.16 fixed point, unwinding it smoothly.
+    /// A good starting point is a gain around `ki / kp`.
+    BackCalculation { gain: i32 },
+    /// The integral is only accumulated while the output is unsaturated, or when the error
+    /// drives it out of saturation. The integral term is also clamped to the output limits.
+    ConditionalIntegration,
+}
+
+/// Integer PID controller with gains in Q16.16 fixed point, i.e. a gain of `1 << 16` is 1.0.
+///
+/// The controller assumes it is updated at a fixed rate, so the integral and derivative
+/// gains are per update rather than per second. The integral is accumulated after applying
+/// the integral gain, so gains can be changed on the fly without bumping the output.
+///
+/// Integral windup is limited by [`AntiWindup::Clamping`] unless another strategy is
+/// selected with [`Pid::set_anti_windup`].
+pub struct Pid {
+    kp: i32,
+    ki: i32,
+    kd: i32,
+    out_min: i32,
+    out_max: i32,
+    anti_windup: AntiWindup,
+    /// Integral term in output units, Q16
+    integral: i64,
+    prev_error: Option<i32>,
+}
+
+impl Pid {
+
+    pub fn new(kp: i32, ki: i32, kd: i32) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            out_min: i32::MIN,
+            out_max: i32::MAX,
+            anti_windup: AntiWindup::Clamping,
+            integral: 0,
+            prev_error: None,
+        }
+    }
+
+    pub fn set_gains(&mut self, kp: i32, ki: i32, kd: i32) {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    pub fn get_gains(&self) -> PidGains {
+        PidGains { kp: self.kp, ki: self.ki, kd: self.kd }
+    }
+
+    /// Limits the output to `min..=max`. The integral term is clamped to the same range.
+    pub fn set_output_limits(&mut self, min: i32, max: i32) {
+        self.out_min = min;
+        self.out_max = max.max(min);
+    }
+
+    pub fn set_anti_windup(&mut self, anti_windup: AntiWindup) {
+        self.anti_windup = anti_windup;
+    }
+
+    /// Clears the integral and derivative history.
+    pub fn reset(&mut self) {
+        self.integral = 0;
+        self.prev_error = None;
+    }
+
+    /// Sets the integral so the output starts at `output` with zero error, for a bumpless
+    /// handover from open loop control. Clamped to the output limits.
+    pub fn preload(&mut self, output: i32) {
+        self.integral = i64::from(output.clamp(self.out_min, self.out_max)) << GAIN_FRAC_BITS;
+        self.prev_error = None;
+    }
+
+    /// Runs one update of the controller, returning the new output.
+    pub fn update(&mut self, setpoint: i32, measurement: i32) -> i32 {
+        let error = setpoint.saturating_sub(measurement);
+
+        let derivative = match self.prev_error {
+            Some(prev_error) => i64::from(error) - i64::from(prev_error),
+            None => 0,
+        };
+        self.prev_error = Some(error);
+
+        // All terms in output units, Q16
+        let min = i128::from(self.out_min) << GAIN_FRAC_BITS;
+        let max = i128::from(self.out_max) << GAIN_FRAC_BITS;
+        let proportional = i128::from(self.kp) * i128::from(error);
+        let derivative = i128::from(self.kd) * i128::from(derivative);
+        let increment = i128::from(self.ki) * i128::from(error);
+        let integral = i128::from(self.integral);
+
+        let output = match self.anti_windup {
+            AntiWindup::Clamping => {
+                let integral = (integral + increment).clamp(min, max);
+                self.integral = integral as i64;
+
+                proportional + integral + derivative
+            },
+            AntiWindup::ConditionalIntegration => {
+                let unsaturated = proportional + integral + increment + derivative;
+                let winding_up = (unsaturated > max && error > 0) || (unsaturated < min && error < 0);
+
+                let integral = if winding_up { integral } else { integral + increment };
+                let integral = integral.clamp(min, max);
+                self.integral = integral as i64;
+
+                proportional + integral + derivative
+            },
+            AntiWindup::BackCalculation { gain } => {
+                let unsaturated = proportional + integral + derivative;
+                let saturated = unsaturated.clamp(min, max);
+                let tracking = (i128::from(gain) * (saturated - unsaturated)) >> GAIN_FRAC_BITS;
+
+                let integral = integral + increment + tracking;
+                self.integral = integral.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64;
+
+                unsaturated
+            },
+        };
+
+        (output >> GAIN_FRAC_BITS).clamp(i128::from(self.out_min), i128::from(self.out_max)) as i32
+    }
+}
+
+/// PID gains scheduled over speed, for plants whose dynamics change across the speed range.
+///
+/// Each of the `N` points pairs a speed with the gains to use at it. Between points the
+/// gains are linearly interpolated, beyond the first and last points they are held.
+#[derive(Clone, Copy)]
+pub struct GainSchedule<const N: usize> {
+    points: [(u32, PidGains); N],
+}
+
+impl<const N: usize> GainSchedule<N> {
+
+    /// `points` must be sorted by ascending speed.
+    pub fn new(points: [(u32, PidGains); N]) -> Self {
+        Self { points }
+    }
+
+    /// The gains at `speed`
+    pub fn gains_at(&self, speed: u32) -> Option<PidGains> {
+        let (first, rest) = self.points.split_first()?;
+        if speed <= first.0 {
+            return Some(first.1);
+        }
+
+        let mut lower = first;
+        for upper in rest {
+            if speed <= upper.0 {
+                let span = i64::from(upper.0 - lower.0).max(1);
+                let offset = i64::from(speed - lower.0);
+                let lerp = |a: i32, b: i32| {
+                    (i64::from(a) + (i64::from(b) - i64::from(a)) * offset / span) as i32
+                };
+
+                return Some(PidGains {
+                    kp: lerp(lower.1.kp, upper.1.kp),
+                    ki: lerp(lower.1.ki, upper.1.ki),
+                    kd: lerp(lower.1.kd, upper.1.kd),
+                });
+            }
+            lower = upper;
+        }
+
+        Some(lower.1)
+    }
+}
+
+impl GainSchedule<1> {
+
+    /// A single, fixed set of gains
+    pub fn fixed(gains: PidGains) -> Self {
+        Self { points: [(0, gains)] }
+    }
+}
+
+/// Moves `current` toward `target` by at most `max_step`
+pub(crate) fn slew_toward(current: i32, target: i32, max_step: u32) -> i32 {
+    let delta = i64::from(target) - i64::from(current);
+    let delta = delta.clamp(-i64::from(max_step), i64::from(max_step));
+
+    (i64::from(current) + delta) as i32
+}
@@ -0,0 +1,260 @@
+use crate::{IntoMillis, Clock, HBridgeDriver, CurrentSensor, Command, Direction, StopMode, Fault, Error};
+
+/// Settings for a [`PluggingBrake`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PluggingSettings {
+    /// Throttle of the reverse torque pulse
+    pub throttle: u16,
+    /// Longest the reverse torque is applied for, in milliseconds
+    pub max_duration_ms: u32,
+    /// Plugging draws far more current than driving, it is cut short above this, in
+    /// milliamps
+    pub max_current_ma: u32,
+}
+
+impl PluggingSettings {
+
+    pub fn new(throttle: u16, max_duration: impl IntoMillis, max_current_ma: u32) -> Self {
+        Self { throttle, max_duration_ms: max_duration.into_millis(), max_current_ma }
+    }
+}
+
+
+/// Brakes by plugging: briefly driving the motor against its rotation, stopping a heavy
+/// load in a shorter distance than a fast motor stop alone.
+///
+/// A brake command while driving starts plugging, ending with a fast motor stop once the
+/// motor has stopped (see [`PluggingBrake::poll`]), the maximum duration has passed, or the
+/// current exceeds its limit, whichever comes first. Coast commands coast immediately.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct PluggingBrake<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    motor: M,
+    sensor: S,
+    settings: PluggingSettings,
+    /// Direction being driven, `None` while stopped
+    direction: Option<Direction>,
+    /// Direction of rotation being plugged, and when plugging started
+    plugging: Option<(Direction, u32)>,
+    enabled: bool,
+}
+
+impl<M, S> PluggingBrake<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+
+    pub fn new(motor: M, sensor: S, settings: PluggingSettings) -> Self {
+        Self { motor, sensor, settings, direction: None, plugging: None, enabled: true }
+    }
+
+    /// With plugging disabled, brake commands are a plain fast motor stop
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_settings(&mut self, settings: PluggingSettings) {
+        self.settings = settings;
+    }
+
+    /// Sends `cmd` to the motor, plugging instead of braking if the motor was being driven.
+    pub fn set(&mut self, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.plugging = None;
+
+        match (cmd, self.direction) {
+            (Command::Drive { direction, throttle }, _) => {
+                // A zero throttle leaves the motor unpowered, so there's nothing to plug
+                self.direction = (throttle != 0).then_some(direction);
+                self.motor.set(cmd)
+            }
+            (Command::Stop(StopMode::Brake), Some(direction)) if self.enabled => {
+                self.direction = None;
+                self.plugging = Some((direction, now_ms));
+                self.motor.set(Command::Drive { direction: opposite(direction), throttle: self.settings.throttle })
+            }
+            (Command::Stop(_), _) => {
+                self.direction = None;
+                self.motor.set(cmd)
+            }
+        }
+    }
+
+    /// Ends plugging once it has run its course, checking the current. Must be called often
+    /// while plugging, the motor reverses if it is plugged for too long.
+    ///
+    /// `speed` is the measured speed if there is an encoder, plugging ends as soon as it
+    /// reaches zero or changes sign. Returns whether the motor is still being plugged.
+    pub fn poll(&mut self, clock: &impl Clock, speed: Option<i32>) -> Result<bool, Error> {
+        let now_ms = clock.now_ms();
+        let Some((direction, start_ms)) = self.plugging else {
+            return Ok(false);
+        };
+
+        let stopped = speed.is_some_and(|speed| match direction {
+            Direction::Forward => speed <= 0,
+            Direction::Reverse => speed >= 0,
+        });
+        let expired = now_ms.wrapping_sub(start_ms) >= self.settings.max_duration_ms;
+        let over_current = self.sensor.current_ma() > self.settings.max_current_ma;
+
+        if stopped || expired || over_current {
+            self.plugging = None;
+            self.motor.set(Command::Stop(StopMode::Brake))?;
+        }
+
+        Ok(self.plugging.is_some())
+    }
+
+    pub fn is_plugging(&self) -> bool {
+        self.plugging.is_some()
+    }
+
+    pub fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Releases the underlying motor driver and current sensor.
+    pub fn free(self) -> (M, S) {
+        (self.motor, self.sensor)
+    }
+}
+
+fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Forward => Direction::Reverse,
+        Direction::Reverse => Direction::Forward,
+    }
+}
+
+/// How a [`HillHold`] holds the motor still
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HoldMode {
+    /// Fast motor stop, the back-EMF of the shorted motor resists rolling
+    Brake,
+    /// Drive against the rolling at this throttle while rolling, then brake
+    CounterTorque { throttle: u16 },
+}
+
+/// Holds a motor still against rolling back, e.g. on a ramp, while it is commanded to stop.
+///
+/// Fed with the measured speed through [`HillHold::report_speed`], it applies the hold once
+/// the motor rolls faster than the threshold while commanded to a standstill, releasing it
+/// on the next drive command.
+pub struct HillHold<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    mode: HoldMode,
+    /// Speed in counts/s the motor may creep at without holding
+    threshold: u32,
+    enabled: bool,
+    /// Whether the last command asked for a standstill
+    stopped: bool,
+    holding: bool,
+}
+
+impl<M> HillHold<M>
+where
+    M: HBridgeDriver,
+{
+
+    pub fn new(motor: M, mode: HoldMode, threshold: u32) -> Self {
+        Self { motor, mode, threshold, enabled: true, stopped: true, holding: false }
+    }
+
+    pub fn set_mode(&mut self, mode: HoldMode) {
+        self.mode = mode;
+    }
+
+    /// Disabling releases any hold, coasting the motor
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        self.enabled = enabled;
+
+        if !enabled && self.holding {
+            self.holding = false;
+            return self.motor.set(Command::Stop(StopMode::Coast));
+        }
+
+        Ok(())
+    }
+
+    /// Holds the motor if it rolls while commanded to a standstill. Must be called every time
+    /// the speed, in counts/s, is measured.
+    pub fn report_speed(&mut self, speed: i32) -> Result<(), Error> {
+        if !self.enabled || !self.stopped {
+            return Ok(());
+        }
+
+        let rolling = speed.unsigned_abs() > self.threshold;
+        if !rolling && !self.holding {
+            return Ok(());
+        }
+        self.holding = true;
+
+        match self.mode {
+            HoldMode::CounterTorque { throttle } if rolling => {
+                let direction = if speed > 0 { Direction::Reverse } else { Direction::Forward };
+                self.motor.set(Command::Drive { direction, throttle })
+            }
+            _ => self.motor.set(Command::Stop(StopMode::Brake)),
+        }
+    }
+
+    /// Whether the hold is applied
+    pub fn is_holding(&self) -> bool {
+        self.holding
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M> HBridgeDriver for HillHold<M>
+where
+    M: HBridgeDriver,
+{
+    /// Passes `cmd` to the motor, releasing the hold on a drive command
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        self.stopped = match cmd {
+            Command::Drive { throttle, .. } => throttle == 0,
+            Command::Stop(_) => true,
+        };
+
+        if !self.stopped {
+            self.holding = false;
+        }
+
+        self.motor.set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.holding = false;
+        self.stopped = true;
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
@@ -0,0 +1,114 @@
+use embedded_hal::digital;
+use core::convert::Infallible;
+
+use crate::{HBridgeDriver, Command, StopMode, Fault, Error};
+
+/// Manages a "power rail" output switching the motor supply, e.g. through a relay or
+/// high-side switch, for boards where it can be cut independently of the logic supply.
+///
+/// The rail is only energized after the motor driver has been put into a safe, coasting
+/// state, and is de-energized as soon as the driver (or any driver it wraps, such as a
+/// [`FaultMonitor`]) reports a latched fault.
+///
+/// [`FaultMonitor`]: crate::FaultMonitor
+pub struct PowerRail<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::OutputPin<Error = Infallible>,
+{
+    motor: M,
+    rail: P,
+    powered: bool,
+}
+
+impl<M, P> PowerRail<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::OutputPin<Error = Infallible>,
+{
+
+    /// Wraps `motor`, de-energizing `rail` until [`PowerRail::power_up`].
+    pub fn new(motor: M, mut rail: P) -> Result<Self, Error> {
+        rail.set_low()?;
+
+        Ok(Self { motor, rail, powered: false })
+    }
+
+    /// Coasts the motor, then energizes the rail. Refuses while a fault is latched,
+    /// returning whether the rail is energized.
+    pub fn power_up(&mut self) -> Result<bool, Error> {
+        if !self.motor.latched_faults().is_empty() {
+            return Ok(false);
+        }
+
+        self.motor.set(Command::Stop(StopMode::Coast))?;
+        self.rail.set_high()?;
+        self.powered = true;
+
+        Ok(true)
+    }
+
+    /// Coasts the motor and de-energizes the rail.
+    pub fn power_down(&mut self) -> Result<(), Error> {
+        self.motor.set(Command::Stop(StopMode::Coast))?;
+        self.rail.set_low()?;
+        self.powered = false;
+
+        Ok(())
+    }
+
+    /// De-energizes the rail if a fault has been latched. Needed when faults are latched
+    /// outside of [`HBridgeDriver::set`], e.g. by reporting them to a `FaultMonitor`.
+    pub fn poll(&mut self) -> Result<(), Error> {
+        if self.powered && !self.motor.latched_faults().is_empty() {
+            self.power_down()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    /// Releases the underlying motor driver and rail pin.
+    pub fn free(self) -> (M, P) {
+        (self.motor, self.rail)
+    }
+}
+
+impl<M, P> HBridgeDriver for PowerRail<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::OutputPin<Error = Infallible>,
+{
+    /// Passes `cmd` to the motor, then de-energizes the rail if the motor latched a fault,
+    /// even when it rejected the command
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        let result = self.motor.set(cmd);
+        self.poll()?;
+
+        result
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Reinitializes the motor, leaving the rail as it is
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
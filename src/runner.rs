@@ -0,0 +1,152 @@
+use crate::{HBridgeDriver, EncoderReader, CurrentSensor, TorqueController, VelocityController, Telemetry, TelemetrySink, Error};
+
+/// A control loop that is updated at a fixed rate, e.g. by a [`ControlRunner`].
+///
+/// Implemented by the crate's controllers, and by closures for anything else that needs
+/// updating at a fixed rate, such as ramping a setpoint.
+pub trait ControlLoop {
+    fn update(&mut self) -> Result<(), Error>;
+}
+
+impl<F> ControlLoop for F
+where
+    F: FnMut() -> Result<(), Error>,
+{
+    fn update(&mut self) -> Result<(), Error> {
+        self()
+    }
+}
+
+impl<M, S> ControlLoop for TorqueController<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    fn update(&mut self) -> Result<(), Error> {
+        TorqueController::update(self)
+    }
+}
+
+impl<M, E, const N: usize> ControlLoop for VelocityController<M, E, N>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+    fn update(&mut self) -> Result<(), Error> {
+        VelocityController::update(self)
+    }
+}
+
+/// Inputs to one [`ControlStep::control_step`]
+#[derive(Clone, Copy)]
+pub struct StepInputs {
+    /// The timestamp of this step, recorded in the returned [`Telemetry`]
+    pub timestamp_ms: u32,
+    /// A new setpoint in the controller's units, `None` keeps the current one
+    pub setpoint: Option<i32>,
+}
+
+/// A single entry point running a whole control cycle in a fixed order: apply the new
+/// setpoint, sense, ramp, run the loop, drive the output, then capture the cycle's telemetry.
+///
+/// Meant for binding a controller into a fixed rate interrupt without having to call the
+/// individual methods in the right order.
+pub trait ControlStep: ControlLoop {
+    fn control_step(&mut self, inputs: StepInputs) -> Result<Telemetry, Error>;
+
+    /// Runs [`ControlStep::control_step`] and records the cycle's telemetry into `sink`
+    fn control_step_into<S>(&mut self, inputs: StepInputs, sink: &mut S) -> Result<Telemetry, Error>
+    where
+        Self: Sized,
+        S: TelemetrySink + ?Sized,
+    {
+        let telemetry = self.control_step(inputs)?;
+        sink.record(&telemetry);
+
+        Ok(telemetry)
+    }
+}
+
+/// The setpoint is the speed in encoder counts/s, see [`VelocityController::set_speed`]
+impl<M, E, const N: usize> ControlStep for VelocityController<M, E, N>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+    fn control_step(&mut self, inputs: StepInputs) -> Result<Telemetry, Error> {
+        if let Some(setpoint) = inputs.setpoint {
+            self.set_speed(setpoint);
+        }
+
+        VelocityController::update(self)?;
+        Ok(self.telemetry(inputs.timestamp_ms))
+    }
+}
+
+/// The setpoint is the current in milliamps, see [`TorqueController::set_torque_ma`]
+impl<M, S> ControlStep for TorqueController<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+    fn control_step(&mut self, inputs: StepInputs) -> Result<Telemetry, Error> {
+        if let Some(setpoint) = inputs.setpoint {
+            self.set_torque_ma(setpoint);
+        }
+
+        TorqueController::update(self)?;
+        Ok(self.telemetry(inputs.timestamp_ms))
+    }
+}
+
+/// Runs `N` control loops at independent rates from a single fixed rate tick, e.g. the
+/// current loop at 1 kHz and the velocity loop at 100 Hz from a 1 kHz timer interrupt.
+///
+/// Each rate is achieved by dividing the tick rate down, rounding to the nearest whole
+/// divider, so rates should divide the tick rate evenly to be exact.
+pub struct ControlRunner<const N: usize> {
+    /// Tick divider of each loop, `None` for disabled loops
+    dividers: [Option<u32>; N],
+    counts: [u32; N],
+}
+
+impl<const N: usize> ControlRunner<N> {
+
+    /// `tick_hz` is the rate [`ControlRunner::tick`] will be called at, `rates_hz[i]` the
+    /// rate the `i`th loop should run at. Rates above the tick rate run every tick, loops
+    /// with a rate of 0 never run.
+    pub fn new(tick_hz: u32, rates_hz: [u32; N]) -> Self {
+        let dividers = rates_hz.map(|rate_hz| match rate_hz {
+            0 => None,
+            rate_hz => Some((tick_hz.saturating_add(rate_hz / 2) / rate_hz).max(1)),
+        });
+
+        Self { dividers, counts: [0; N] }
+    }
+
+    /// Advances by one tick, updating every loop that is due, in index order. The loops
+    /// must be passed in the same order as their rates were given to [`ControlRunner::new`].
+    ///
+    /// Loops that are due on the same tick all run on that tick, so order faster inner loops
+    /// (e.g. current) after slower outer loops (e.g. velocity) to use their latest output.
+    pub fn tick(&mut self, loops: [&mut dyn ControlLoop; N]) -> Result<(), Error> {
+        for ((control_loop, count), divider) in loops.into_iter().zip(self.counts.iter_mut()).zip(self.dividers) {
+            let Some(divider) = divider else {
+                continue;
+            };
+
+            *count += 1;
+            if *count >= divider {
+                *count = 0;
+                control_loop.update()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restarts every divider, so all enabled loops run on the next tick.
+    pub fn reset(&mut self) {
+        self.counts = self.dividers.map(|divider| divider.map_or(0, |divider| divider - 1));
+    }
+}
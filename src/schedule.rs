@@ -0,0 +1,376 @@
+use core::cmp::Reverse;
+
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, Direction, StopMode, MotorBank, Error};
+
+/// A set of motors addressed by index, which a [`CommandScheduler`] can drive.
+///
+/// Commands for an index that doesn't exist fail with [`Error::ConfigInvalid`].
+pub trait MotorGroup {
+    fn set_motor(&mut self, index: usize, cmd: Command) -> Result<(), Error>;
+}
+
+/// A single motor is a group of one, at index 0
+impl<T> MotorGroup for T
+where
+    T: HBridgeDriver,
+{
+    fn set_motor(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        if index == 0 { self.set(cmd) } else { Err(Error::ConfigInvalid) }
+    }
+}
+
+impl<D, const N: usize> MotorGroup for MotorBank<D, N>
+where
+    D: HBridgeDriver,
+{
+    fn set_motor(&mut self, index: usize, cmd: Command) -> Result<(), Error> {
+        self.checked_set(index, cmd).unwrap_or(Err(Error::ConfigInvalid))
+    }
+}
+
+/// A [`Command`] for the motor at `motor` in a [`MotorGroup`], due `at_ms` after the start
+/// of a sequence
+///
+/// Orders by time first, so a sequence can be sorted for a [`CommandScheduler`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimedCommand {
+    pub at_ms: u32,
+    pub motor: u8,
+    pub command: Command,
+}
+
+/// Plays a sequence of [`TimedCommand`]s, sorted by time, into a [`MotorGroup`].
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct CommandScheduler<'a> {
+    sequence: &'a [TimedCommand],
+    next: usize,
+    start_ms: Option<u32>,
+}
+
+impl<'a> CommandScheduler<'a> {
+
+    pub fn new(sequence: &'a [TimedCommand]) -> Self {
+        Self { sequence, next: 0, start_ms: None }
+    }
+
+    /// Starts (or restarts) playing the sequence from its beginning, as read from `clock`
+    pub fn start(&mut self, clock: &impl Clock) {
+        let now_ms = clock.now_ms();
+        self.next = 0;
+        self.start_ms = Some(now_ms);
+    }
+
+    /// Stops playing, leaving the motors as they are
+    pub fn cancel(&mut self) {
+        self.start_ms = None;
+    }
+
+    /// Applies every command which has fallen due. Must be called at least as often as the
+    /// timing resolution needed. Returns `Ok(true)` once the whole sequence has been played.
+    pub fn poll<G>(&mut self, group: &mut G, clock: &impl Clock) -> Result<bool, Error>
+    where
+        G: MotorGroup + ?Sized,
+    {
+        let now_ms = clock.now_ms();
+        let Some(start_ms) = self.start_ms else {
+            return Ok(self.is_finished());
+        };
+        let elapsed = now_ms.wrapping_sub(start_ms);
+
+        while let Some(timed) = self.sequence.get(self.next) {
+            if timed.at_ms > elapsed {
+                break;
+            }
+
+            self.next += 1;
+            group.set_motor(usize::from(timed.motor), timed.command)?;
+        }
+
+        Ok(self.is_finished())
+    }
+
+    /// Whether a sequence is being played
+    pub fn is_running(&self) -> bool {
+        self.start_ms.is_some() && !self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.sequence.len()
+    }
+}
+
+/// Holds up to `N` [`TimedCommand`]s tagged with absolute activation times, applying each
+/// to a [`MotorGroup`] once its time has come, e.g. for choreography synchronized across
+/// motors or aligned with an external event like a sensor trigger or frame sync.
+///
+/// Unlike a [`CommandScheduler`], `at_ms` is a timestamp from the same [`Clock`] passed to
+/// [`CommandQueue::poll`], not an offset, and commands can be added at any time in any
+/// order. Commands are due once the clock has reached them, up to about 24 days ahead, so wrapping is handled.
+pub struct CommandQueue<const N: usize> {
+    /// Queued commands with the sequence number they were queued under
+    commands: [Option<(TimedCommand, u32)>; N],
+    next_sequence: u32,
+}
+
+impl<const N: usize> CommandQueue<N> {
+
+    pub const fn new() -> Self {
+        Self { commands: [None; N], next_sequence: 0 }
+    }
+
+    /// Queues `timed` to be applied at its absolute time, handing it back if the queue is full
+    pub fn schedule(&mut self, timed: TimedCommand) -> Result<(), TimedCommand> {
+        match self.commands.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some((timed, self.next_sequence));
+                self.next_sequence = self.next_sequence.wrapping_add(1);
+                Ok(())
+            }
+            None => Err(timed),
+        }
+    }
+
+    /// Applies every command which has fallen due, earliest first and in the order queued
+    /// for the same time. Must be called at least as often as the timing resolution needed.
+    /// Returns whether any are still queued.
+    pub fn poll<G>(&mut self, group: &mut G, clock: &impl Clock) -> Result<bool, Error>
+    where
+        G: MotorGroup + ?Sized,
+    {
+        let now_ms = clock.now_ms();
+        let next_sequence = self.next_sequence;
+        // Signed lateness, negative while still in the future, then how long ago it was queued
+        let order = |&(timed, sequence): &(TimedCommand, u32)| {
+            (now_ms.wrapping_sub(timed.at_ms) as i32, next_sequence.wrapping_sub(sequence))
+        };
+
+        while let Some(slot) = self.commands.iter_mut()
+            .filter(|slot| slot.as_ref().is_some_and(|entry| order(entry).0 >= 0))
+            .min_by_key(|slot| Reverse(slot.as_ref().map(order)))
+        {
+            if let Some((timed, _)) = slot.take() {
+                group.set_motor(usize::from(timed.motor), timed.command)?;
+            }
+        }
+
+        Ok(!self.is_empty())
+    }
+
+    /// Drops every queued command, leaving the motors as they are
+    pub fn cancel(&mut self) {
+        self.commands = [None; N];
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.iter().all(Option::is_none)
+    }
+}
+
+impl<const N: usize> Default for CommandQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps a [`MotorGroup`], recording every command applied through it into a user provided
+/// buffer so it can be replayed later with a [`CommandScheduler`] ("teach and repeat").
+///
+/// Recording stops silently once the buffer is full, see [`CommandRecorder::is_full`].
+pub struct CommandRecorder<'a, G>
+where
+    G: MotorGroup,
+{
+    group: G,
+    buffer: &'a mut [TimedCommand],
+    len: usize,
+    start_ms: Option<u32>,
+}
+
+impl<'a, G> CommandRecorder<'a, G>
+where
+    G: MotorGroup,
+{
+
+    pub fn new(group: G, buffer: &'a mut [TimedCommand]) -> Self {
+        Self { group, buffer, len: 0, start_ms: None }
+    }
+
+    /// Clears the recording and starts a new one, as read from `clock`
+    pub fn start(&mut self, clock: &impl Clock) {
+        let now_ms = clock.now_ms();
+        self.len = 0;
+        self.start_ms = Some(now_ms);
+    }
+
+    /// Stops recording, commands are still applied
+    pub fn stop(&mut self) {
+        self.start_ms = None;
+    }
+
+    /// Sends `cmd` to the motor at `index`, recording it if a recording is in progress and
+    /// the motor accepted it
+    pub fn set(&mut self, index: u8, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.group.set_motor(usize::from(index), cmd)?;
+
+        if let Some(start_ms) = self.start_ms
+            && let Some(slot) = self.buffer.get_mut(self.len)
+        {
+            *slot = TimedCommand { at_ms: now_ms.wrapping_sub(start_ms), motor: index, command: cmd };
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    /// The commands recorded so far, ready for a [`CommandScheduler`]
+    pub fn recording(&self) -> &[TimedCommand] {
+        &self.buffer[..self.len]
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= self.buffer.len()
+    }
+
+    pub fn group(&mut self) -> &mut G {
+        &mut self.group
+    }
+
+    /// Releases the motor group, the recording is the first `len` entries of the buffer.
+    pub fn free(self) -> (G, usize) {
+        (self.group, self.len)
+    }
+}
+
+/// Fluent builder for a timed two-motor sequence, e.g. for a [`DifferentialDrive`] (left
+/// motor index 0, right index 1), to be played with a [`CommandScheduler`], e.g.
+/// `Maneuver::<8>::new().forward(u16::MAX, 2000).brake(500).spin_right(55000, 2000)`.
+///
+/// Each step lasts until the next one begins, and both motors coast when the last step ends
+/// unless changed with [`Maneuver::end_with`].
+/// `N` is the number of [`TimedCommand`]s held, which must be at least two per step plus two.
+/// Steps which don't fit are dropped and [`Maneuver::sequence`] fails.
+///
+/// [`DifferentialDrive`]: crate::DifferentialDrive
+#[derive(Clone, Copy)]
+pub struct Maneuver<const N: usize> {
+    commands: [TimedCommand; N],
+    len: usize,
+    duration_ms: u32,
+    end: StopMode,
+    /// A step didn't fit in `N` commands
+    overflowed: bool,
+}
+
+impl<const N: usize> Maneuver<N> {
+
+    pub const fn new() -> Self {
+        let coast = TimedCommand { at_ms: 0, motor: 0, command: Command::Stop(StopMode::Coast) };
+        Self { commands: [coast; N], len: 0, duration_ms: 0, end: StopMode::Coast, overflowed: false }
+    }
+
+    /// Drives both motors forward
+    pub fn forward(self, throttle: u16, duration: impl IntoMillis) -> Self {
+        self.drive(Direction::Forward, Direction::Forward, throttle, duration)
+    }
+
+    /// Drives both motors in reverse
+    pub fn reverse(self, throttle: u16, duration: impl IntoMillis) -> Self {
+        self.drive(Direction::Reverse, Direction::Reverse, throttle, duration)
+    }
+
+    /// Spins anticlockwise on the spot
+    pub fn spin_left(self, throttle: u16, duration: impl IntoMillis) -> Self {
+        self.drive(Direction::Reverse, Direction::Forward, throttle, duration)
+    }
+
+    /// Spins clockwise on the spot
+    pub fn spin_right(self, throttle: u16, duration: impl IntoMillis) -> Self {
+        self.drive(Direction::Forward, Direction::Reverse, throttle, duration)
+    }
+
+    /// Brakes both motors
+    pub fn brake(self, duration: impl IntoMillis) -> Self {
+        let brake = Command::Stop(StopMode::Brake);
+        self.step(brake, brake, duration)
+    }
+
+    /// Lets both motors coast
+    pub fn coast(self, duration: impl IntoMillis) -> Self {
+        let coast = Command::Stop(StopMode::Coast);
+        self.step(coast, coast, duration)
+    }
+
+    /// Applies `left` and `right` for `duration`. If the step doesn't fit in `N` commands,
+    /// it and every later step are dropped, see [`Maneuver::sequence`].
+    pub fn step(mut self, left: Command, right: Command, duration: impl IntoMillis) -> Self {
+        let duration_ms = duration.into_millis();
+
+        // Replace the trailing coast with this step, then end on a coast again
+        let len = self.len.saturating_sub(2);
+        if self.overflowed || len + 4 > N {
+            self.overflowed = true;
+            return self;
+        }
+        self.len = len;
+
+        self.push(0, left);
+        self.push(1, right);
+
+        self.duration_ms = self.duration_ms.saturating_add(duration_ms);
+        self.push(0, Command::Stop(self.end));
+        self.push(1, Command::Stop(self.end));
+
+        self
+    }
+
+    /// Stops both motors using `stop_mode` when the last step ends
+    pub fn end_with(mut self, stop_mode: StopMode) -> Self {
+        self.end = stop_mode;
+        if self.len >= 2 {
+            self.commands[self.len - 2].command = Command::Stop(stop_mode);
+            self.commands[self.len - 1].command = Command::Stop(stop_mode);
+        }
+
+        self
+    }
+
+    /// The total length of the sequence
+    pub fn duration_ms(&self) -> u32 {
+        self.duration_ms
+    }
+
+    /// The sequence, ready for a [`CommandScheduler`], or [`Error::ConfigInvalid`] if steps
+    /// were dropped for not fitting in `N` commands
+    pub fn sequence(&self) -> Result<&[TimedCommand], Error> {
+        if self.overflowed {
+            return Err(Error::ConfigInvalid);
+        }
+
+        Ok(&self.commands[..self.len])
+    }
+
+    fn drive(self, left: Direction, right: Direction, throttle: u16, duration: impl IntoMillis) -> Self {
+        self.step(
+            Command::Drive { direction: left, throttle },
+            Command::Drive { direction: right, throttle },
+            duration,
+        )
+    }
+
+    fn push(&mut self, motor: u8, command: Command) {
+        self.commands[self.len] = TimedCommand { at_ms: self.duration_ms, motor, command };
+        self.len += 1;
+    }
+}
+
+impl<const N: usize> Default for Maneuver<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Clock, IntoMillis, Command, Direction, StopMode, MotorGroup, TimedCommand, Error};
+
+/// Bounds for the commands of a [`SoakGenerator`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoakSettings {
+    /// Number of motors commanded, at indices `0..motors`. At most 32.
+    pub motors: u8,
+    pub min_throttle: u16,
+    pub max_throttle: u16,
+    /// Shortest time a command is held before the next, in milliseconds. At least 1.
+    pub min_hold_ms: u32,
+    pub max_hold_ms: u32,
+    /// Percentage of drive commands which reverse the motor's direction
+    pub reversal_percent: u8,
+    /// Percentage of commands which stop the motor rather than drive it
+    pub stop_percent: u8,
+    /// Percentage of stops which brake rather than coast
+    pub brake_percent: u8,
+}
+
+impl Default for SoakSettings {
+    fn default() -> Self {
+        Self {
+            motors: 1,
+            min_throttle: u16::MAX / 4,
+            max_throttle: u16::MAX,
+            min_hold_ms: 500,
+            max_hold_ms: 5000,
+            reversal_percent: 20,
+            stop_percent: 10,
+            brake_percent: 50,
+        }
+    }
+}
+
+impl SoakSettings {
+
+    /// Replaces the bounds of the hold times, e.g. with `fugit` durations
+    pub fn with_hold_times(self, min_hold: impl IntoMillis, max_hold: impl IntoMillis) -> Self {
+        Self { min_hold_ms: min_hold.into_millis(), max_hold_ms: max_hold.into_millis(), ..self }
+    }
+}
+
+/// Generates an endless, pseudo-random but bounded sequence of commands for burn-in testing
+/// motors, gearboxes and drivers.
+///
+/// The sequence is reproducible from its seed. It can be iterated as [`TimedCommand`]s, e.g.
+/// to fill a buffer for a [`CommandScheduler`] or to check against a mock, or played
+/// directly into a [`MotorGroup`] with [`SoakGenerator::poll`].
+///
+/// [`CommandScheduler`]: crate::CommandScheduler
+pub struct SoakGenerator {
+    settings: SoakSettings,
+    rng: u32,
+    /// Bit `i` is set while motor `i` is reversed
+    reversed: u32,
+    /// Time of the next command yielded by the iterator, wrapping
+    at_ms: u32,
+    /// When the last polled command was applied, and how long it is held for
+    holding: Option<(u32, u32)>,
+}
+
+impl SoakGenerator {
+
+    /// Fails with [`Error::ConfigInvalid`] if `settings.motors` isn't in `1..=32`, or a
+    /// minimum is above its maximum.
+    pub fn new(settings: SoakSettings, seed: u32) -> Result<Self, Error> {
+        if !(1..=32).contains(&settings.motors)
+            || settings.min_throttle > settings.max_throttle
+            || settings.min_hold_ms > settings.max_hold_ms
+        {
+            return Err(Error::ConfigInvalid);
+        }
+
+        Ok(Self {
+            settings: SoakSettings {
+                min_hold_ms: settings.min_hold_ms.max(1),
+                max_hold_ms: settings.max_hold_ms.max(1),
+                ..settings
+            },
+            // Xorshift gets stuck at zero
+            rng: if seed == 0 { 0x9E37_79B9 } else { seed },
+            reversed: 0,
+            at_ms: 0,
+            holding: None,
+        })
+    }
+
+    /// Applies the next command once the previous one has been held for its time. Must be
+    /// called at least as often as the timing resolution needed.
+    pub fn poll<G>(&mut self, group: &mut G, clock: &impl Clock) -> Result<(), Error>
+    where
+        G: MotorGroup + ?Sized,
+    {
+        let now_ms = clock.now_ms();
+        if let Some((applied_ms, hold_ms)) = self.holding
+            && now_ms.wrapping_sub(applied_ms) < hold_ms
+        {
+            return Ok(());
+        }
+
+        let (motor, command, hold_ms) = self.next_step();
+        self.holding = Some((now_ms, hold_ms));
+
+        group.set_motor(usize::from(motor), command)
+    }
+
+    pub fn settings(&self) -> &SoakSettings {
+        &self.settings
+    }
+
+    /// Picks the next motor, its command and how long to hold it for
+    fn next_step(&mut self) -> (u8, Command, u32) {
+        let settings = self.settings;
+
+        let motor = self.range(0, u32::from(settings.motors) - 1) as u8;
+        let hold_ms = self.range(settings.min_hold_ms, settings.max_hold_ms);
+
+        let command = if self.chance(settings.stop_percent) {
+            let mode = if self.chance(settings.brake_percent) { StopMode::Brake } else { StopMode::Coast };
+            Command::Stop(mode)
+        } else {
+            if self.chance(settings.reversal_percent) {
+                self.reversed ^= 1 << motor;
+            }
+
+            let direction = if self.reversed & (1 << motor) != 0 { Direction::Reverse } else { Direction::Forward };
+            let throttle = self.range(u32::from(settings.min_throttle), u32::from(settings.max_throttle)) as u16;
+            Command::Drive { direction, throttle }
+        };
+
+        (motor, command, hold_ms)
+    }
+
+    /// Xorshift32
+    fn next_u32(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+
+    /// Uniform in `min..=max`, give or take modulo bias
+    fn range(&mut self, min: u32, max: u32) -> u32 {
+        let span = u64::from(max - min) + 1;
+        min + (u64::from(self.next_u32()) % span) as u32
+    }
+
+    fn chance(&mut self, percent: u8) -> bool {
+        self.range(0, 99) < u32::from(percent)
+    }
+}
+
+/// Yields commands timed from zero, each due once the previous has been held. The times
+/// wrap after `u32::MAX` milliseconds, like the free running timestamps elsewhere.
+impl Iterator for SoakGenerator {
+    type Item = TimedCommand;
+
+    fn next(&mut self) -> Option<TimedCommand> {
+        let (motor, command, hold_ms) = self.next_step();
+        let timed = TimedCommand { at_ms: self.at_ms, motor, command };
+        self.at_ms = self.at_ms.wrapping_add(hold_ms);
+
+        Some(timed)
+    }
+}
@@ -0,0 +1,287 @@
+use embedded_hal::{digital, pwm};
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use crate::{Clock, IntoMillis, HBridgeDriver, BridgeInputs, Command, Direction, StopMode, PwmFrequency, throttle_percent, Error};
+
+/// Driver for one half of an **L298N** with a slow software modulated enable, for thermal
+/// loads like heaters and Peltier elements, where kHz PWM is unnecessary or harmful.
+///
+/// The enable is a plain GPIO output, switched on for the throttle's share of each period in
+/// 1% steps by [`L298NSlowPwm::poll`]. Commands and throttles are the same as for an
+/// [`L298NHBridge`], so it can be used wherever an [`HBridgeDriver`] is expected.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+///
+/// [`L298NHBridge`]: crate::L298NHBridge
+pub struct L298NSlowPwm<P1, P2, EN>
+where
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: digital::OutputPin<Error = Infallible>,
+{
+    dir1: P1,
+    dir2: P2,
+    enable: EN,
+    period_ms: u32,
+    command: Command,
+    inputs: Option<BridgeInputs>,
+    /// Share of each period the enable is on, in percent
+    on_percent: u8,
+    /// Start of the current period, `None` until the first poll
+    period_start_ms: Option<u32>,
+    enabled: bool,
+}
+
+impl<P1, P2, EN> L298NSlowPwm<P1, P2, EN>
+where
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: digital::OutputPin<Error = Infallible>,
+{
+
+    /// Takes the pins and puts the bridge into the safe state, `period` is the length of
+    /// one modulation period, e.g. a second.
+    pub fn new(dir1: P1, dir2: P2, enable: EN, period: impl IntoMillis) -> Result<Self, Error> {
+        let mut handle = Self {
+            dir1, dir2, enable,
+            period_ms: period.into_millis().max(1),
+            command: Command::Stop(StopMode::Coast),
+            inputs: None,
+            on_percent: 0,
+            period_start_ms: None,
+            enabled: false,
+        };
+        handle.reinit()?;
+
+        Ok(handle)
+    }
+
+    pub fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd {
+            Command::Drive { direction, throttle } => {
+                match direction {
+                    Direction::Forward => self.set_inputs(BridgeInputs::Forward)?,
+                    Direction::Reverse => self.set_inputs(BridgeInputs::Reverse)?,
+                }
+                self.on_percent = throttle_percent(throttle);
+            },
+            Command::Stop(StopMode::Brake) => {
+                self.set_inputs(BridgeInputs::Brake)?;
+                self.on_percent = 100;
+            },
+            Command::Stop(StopMode::Coast) => self.on_percent = 0,
+        }
+        self.command = cmd;
+
+        // Applied right away at full or no duty, partial duty waits for the next poll
+        match self.on_percent {
+            0 => self.set_enable(false),
+            100 => self.set_enable(true),
+            _ => Ok(()),
+        }
+    }
+
+    /// Switches the enable for the current point in the period. Must be called at least
+    /// every 1% of the period for full resolution.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        let start_ms = *self.period_start_ms.get_or_insert(now_ms);
+        let mut elapsed = now_ms.wrapping_sub(start_ms);
+
+        if elapsed >= self.period_ms {
+            self.period_start_ms = Some(now_ms);
+            elapsed = 0;
+        }
+
+        let on = u64::from(elapsed) * 100 < u64::from(self.period_ms) * u64::from(self.on_percent);
+        self.set_enable(on)
+    }
+
+    /// The commanded drive throttle, zero while stopped
+    pub fn get_throttle(&self) -> u16 {
+        match self.command {
+            Command::Drive { throttle, .. } => throttle,
+            Command::Stop(_) => 0u16,
+        }
+    }
+
+    /// Whether the enable is currently on
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Re-applies the safe state: enable low, then both direction inputs low.
+    pub fn reinit(&mut self) -> Result<(), Error> {
+        self.on_percent = 0;
+        self.enable.set_low()?;
+        self.enabled = false;
+        self.inputs = None;
+        self.set_inputs(BridgeInputs::Off)?;
+        self.command = Command::Stop(StopMode::Coast);
+
+        Ok(())
+    }
+
+    /// Releases the pins, which are left as they are.
+    pub fn free(self) -> (P1, P2, EN) {
+        (self.dir1, self.dir2, self.enable)
+    }
+
+    fn set_enable(&mut self, on: bool) -> Result<(), Error> {
+        if on != self.enabled {
+            self.enable.set_state(on.into())?;
+            self.enabled = on;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the direction inputs, switching the enable off first if they change
+    fn set_inputs(&mut self, inputs: BridgeInputs) -> Result<(), Error> {
+        if self.inputs == Some(inputs) {
+            return Ok(());
+        }
+
+        self.enable.set_low()?;
+        self.enabled = false;
+
+        let (dir1, dir2) = inputs.levels();
+        self.dir1.set_state(dir1.into())?;
+        self.dir2.set_state(dir2.into())?;
+        self.inputs = Some(inputs);
+
+        Ok(())
+    }
+}
+
+impl<P1, P2, EN> HBridgeDriver for L298NSlowPwm<P1, P2, EN>
+where
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: digital::OutputPin<Error = Infallible>,
+{
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        L298NSlowPwm::set(self, cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        L298NSlowPwm::get_throttle(self)
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        L298NSlowPwm::reinit(self)
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        Some(self.command)
+    }
+}
+
+/// Software PWM for boards without a free timer channel, shared between the code setting the
+/// duty and a timer interrupt switching the pin.
+///
+/// [`SoftPwm::channel`] implements [`SetDutyCycle`], e.g. as the enable of an
+/// [`L298NHBridge`], while [`SoftPwm::output`] owns the pin and is ticked at a fixed rate. The
+/// PWM frequency is the tick rate divided by the max duty, so resolution trades against
+/// frequency: at a 20 kHz tick, a max duty of 100 gives 1% steps at 200 Hz.
+///
+/// Only atomic loads and stores are used, so it works on targets without compare-and-swap.
+///
+/// [`SetDutyCycle`]: pwm::SetDutyCycle
+/// [`L298NHBridge`]: crate::L298NHBridge
+pub struct SoftPwm {
+    duty: AtomicU16,
+    max_duty: u16,
+    tick_hz: u32,
+}
+
+impl SoftPwm {
+
+    /// `max_duty` is the number of ticks in a period, at least 1, and `tick_hz` the rate
+    /// the output is ticked at
+    pub const fn new(max_duty: u16, tick_hz: u32) -> Self {
+        Self {
+            duty: AtomicU16::new(0),
+            max_duty: if max_duty == 0 { 1 } else { max_duty },
+            tick_hz,
+        }
+    }
+
+    /// The duty cycle side, for the motor driver
+    pub fn channel(&self) -> SoftPwmChannel<'_> {
+        SoftPwmChannel { pwm: self }
+    }
+
+    /// The pin side, for the timer interrupt
+    pub fn output<P>(&self, pin: P) -> SoftPwmOutput<'_, P>
+    where
+        P: digital::OutputPin<Error = Infallible>,
+    {
+        SoftPwmOutput { pwm: self, pin, tick: 0, high: None }
+    }
+}
+
+/// The duty cycle side of a [`SoftPwm`]
+pub struct SoftPwmChannel<'a> {
+    pwm: &'a SoftPwm,
+}
+
+impl pwm::ErrorType for SoftPwmChannel<'_> {
+    type Error = Infallible;
+}
+
+impl pwm::SetDutyCycle for SoftPwmChannel<'_> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.pwm.max_duty
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        self.pwm.duty.store(duty.min(self.pwm.max_duty), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl PwmFrequency for SoftPwmChannel<'_> {
+    fn pwm_frequency_hz(&self) -> u32 {
+        self.pwm.tick_hz / u32::from(self.pwm.max_duty)
+    }
+}
+
+/// The pin side of a [`SoftPwm`]
+pub struct SoftPwmOutput<'a, P>
+where
+    P: digital::OutputPin<Error = Infallible>,
+{
+    pwm: &'a SoftPwm,
+    pin: P,
+    /// Position in the period, in ticks
+    tick: u16,
+    /// The level last written, `None` before the first tick
+    high: Option<bool>,
+}
+
+impl<P> SoftPwmOutput<'_, P>
+where
+    P: digital::OutputPin<Error = Infallible>,
+{
+
+    /// Advances one tick, switching the pin at the start of the period and once the duty
+    /// has elapsed. Must be called at the tick rate, e.g. from a timer interrupt.
+    pub fn tick(&mut self) -> Result<(), Error> {
+        let high = self.tick < self.pwm.duty.load(Ordering::Relaxed);
+        self.tick = if self.tick + 1 >= self.pwm.max_duty { 0 } else { self.tick + 1 };
+
+        if self.high != Some(high) {
+            self.pin.set_state(high.into())?;
+            self.high = Some(high);
+        }
+
+        Ok(())
+    }
+
+    /// Releases the pin, which is left as it is.
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
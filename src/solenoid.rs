@@ -0,0 +1,119 @@
+use embedded_hal::{delay::DelayNs, digital, pwm};
+use core::convert::Infallible;
+
+use crate::{Clock, IntoMillis, L298NHBridge, Command, Direction, StopMode, Error};
+
+/// Hit-and-hold driver for a solenoid (door lock, valve, ...) wired across one half of an
+/// **L298N**.
+///
+/// Energizing applies full duty for the pull-in time so the plunger seats, then drops to
+/// the hold throttle, which only has to overcome the return spring. This keeps the coil
+/// from overheating while it is held.
+///
+/// Timing can either be blocking, via [`Solenoid::energize`], or polled from the main loop
+/// via [`Solenoid::start`] and [`Solenoid::poll`].
+pub struct Solenoid<P1, P2, EN>
+where 
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: pwm::SetDutyCycle<Error = Infallible>,
+{
+    bridge: L298NHBridge<P1, P2, EN>,
+    pull_in_ms: u32,
+    hold_throttle: u16,
+    state: SolenoidState,
+}
+
+/// The phase a `Solenoid` is currently in
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum SolenoidState {
+    Released,
+    PullIn { since_ms: u32 },
+    Holding,
+}
+
+impl<P1, P2, EN> Solenoid<P1, P2, EN>
+where 
+    P1: digital::OutputPin<Error = Infallible>,
+    P2: digital::OutputPin<Error = Infallible>,
+    EN: pwm::SetDutyCycle<Error = Infallible>,
+{
+
+    /// Wraps `bridge`, which is assumed to be released (coasting) already.
+    ///
+    /// `pull_in` is how long full duty is applied for, `hold_throttle` is the
+    /// throttle held afterwards.
+    pub fn new(bridge: L298NHBridge<P1, P2, EN>, pull_in: impl IntoMillis, hold_throttle: u16) -> Self {
+        Self { bridge, pull_in_ms: pull_in.into_millis(), hold_throttle, state: SolenoidState::Released }
+    }
+
+    /// Energizes the solenoid, blocking for the pull-in time before dropping to the hold throttle.
+    pub fn energize<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error> {
+        self.drive(u16::MAX)?;
+        delay.delay_ms(self.pull_in_ms);
+        self.drive(self.hold_throttle)?;
+        self.state = SolenoidState::Holding;
+
+        Ok(())
+    }
+
+    /// Starts energizing the solenoid without blocking, timing the pull-in from `clock`.
+    ///
+    /// [`Solenoid::poll`] must then be called regularly to drop to the hold throttle.
+    pub fn start(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.drive(u16::MAX)?;
+        self.state = SolenoidState::PullIn { since_ms: now_ms };
+
+        Ok(())
+    }
+
+    /// Drops to the hold throttle once the pull-in time has elapsed since [`Solenoid::start`].
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        if let SolenoidState::PullIn { since_ms } = self.state
+            && now_ms.wrapping_sub(since_ms) >= self.pull_in_ms
+        {
+            self.drive(self.hold_throttle)?;
+            self.state = SolenoidState::Holding;
+        }
+
+        Ok(())
+    }
+
+    /// De-energizes the solenoid, letting the return spring release it.
+    pub fn release(&mut self) -> Result<(), Error> {
+        self.bridge.set(Command::Stop(StopMode::Coast))?;
+        self.state = SolenoidState::Released;
+
+        Ok(())
+    }
+
+    pub fn set_hold_throttle(&mut self, hold_throttle: u16) -> Result<(), Error> {
+        self.hold_throttle = hold_throttle;
+
+        if self.state == SolenoidState::Holding {
+            self.drive(hold_throttle)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_pull_in(&mut self, pull_in: impl IntoMillis) {
+        self.pull_in_ms = pull_in.into_millis();
+    }
+
+    pub fn get_state(&self) -> SolenoidState {
+        self.state
+    }
+
+    /// Releases the underlying bridge.
+    pub fn free(self) -> L298NHBridge<P1, P2, EN> {
+        self.bridge
+    }
+
+    fn drive(&mut self, throttle: u16) -> Result<(), Error> {
+        self.bridge.set(Command::Drive { direction: Direction::Forward, throttle })
+    }
+}
@@ -0,0 +1,650 @@
+use crate::{HBridgeDriver, EncoderReader, Fault, Command, Direction, StopMode, duty_from_fullscale, Error};
+
+/// Electrical phases per cycle, in eighths of a full step
+const PHASES: usize = 32;
+
+/// Coil polarities `(a, b)` for each half step phase. The odd phases make up the two-phase
+/// full step sequence, the even phases the one-phase wave drive sequence.
+const HALF_STEP: [(i8, i8); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1),
+];
+
+/// Full scale sine over a quarter cycle, in eighths of a full step
+const QUARTER_SINE: [u16; 9] = [0, 12785, 25079, 36409, 46340, 54490, 60546, 64276, 65535];
+
+/// Number of fractional bits in the fixed point step rates
+const RATE_FRAC_BITS: u32 = 16;
+
+/// Driver for a bipolar stepper motor wired across both halves of an **L298N**, one coil
+/// per half.
+///
+/// The coils can be sequenced in any [`StepMode`], two-phase full stepping by default.
+///
+/// Stepping can be done directly with [`Stepper::step`], or without blocking from a timer
+/// interrupt running at the fixed `tick_hz` given to [`Stepper::new`], by calling either:
+/// - [`Stepper::step_tick`]: steps at the constant rate set by [`Stepper::set_step_rate`].
+/// - [`Stepper::run`]: accelerates and decelerates at [`Stepper::set_acceleration`] up to
+///   [`Stepper::set_max_speed`], like Arduino's AccelStepper.
+pub struct Stepper<A, B>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+{
+    coil_a: A,
+    coil_b: B,
+    throttle: u16,
+    mode: StepMode,
+    /// Electrical phase in eighths of a full step, `0..PHASES`
+    phase: usize,
+    /// Position in eighths of a full step, so it survives changes of the step mode
+    position: i64,
+    tick_hz: u32,
+    step_hz: u32,
+    max_speed: u32,
+    acceleration: u32,
+    /// Current speed of [`Stepper::run`] in steps/s, with `RATE_FRAC_BITS` fractional bits
+    speed: u64,
+    accumulator: u64,
+    direction: Direction,
+    motion: Motion,
+    /// Ticks without a step after which the coils drop to the hold throttle
+    idle_hold: Option<(u32, u16)>,
+    idle_ticks: u32,
+    /// The coils are below full current, either held at idle or released
+    reduced: bool,
+}
+
+/// The coil sequence a [`Stepper`] steps through
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StepMode {
+    /// Both coils energized at all times. Full torque.
+    FullStep,
+    /// One coil energized at a time. Half the current of full stepping, at reduced torque.
+    Wave,
+    /// Alternates between one and both coils energized, doubling the resolution.
+    HalfStep,
+    /// Pseudo-microstepping with 4 microsteps per full step. The enable duties of the coils
+    /// follow a sine and cosine, which smooths motion and reduces resonance. Positions
+    /// between full steps are approximate, as the L298N only controls the average voltage
+    /// across a coil rather than its current.
+    MicroStep4,
+    /// Pseudo-microstepping with 8 microsteps per full step, see [`StepMode::MicroStep4`].
+    MicroStep8,
+}
+
+impl StepMode {
+
+    /// Phase increment of a single step, and the phase offset steps are aligned to
+    fn phase_alignment(self) -> (usize, usize) {
+        match self {
+            StepMode::FullStep => (8, 4),
+            StepMode::Wave => (8, 0),
+            StepMode::HalfStep => (4, 0),
+            StepMode::MicroStep4 => (2, 0),
+            StepMode::MicroStep8 => (1, 0),
+        }
+    }
+}
+
+/// What the step generator is currently working through
+enum Motion {
+    Idle,
+    /// Signed number of steps left, positive is forward
+    Steps(i64),
+    Continuous(Direction),
+}
+
+impl<A, B> Stepper<A, B>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+{
+
+    /// Wraps the drivers of the two coils. The coils are not energized until the first step.
+    ///
+    /// `tick_hz` is the rate [`Stepper::step_tick`] or [`Stepper::run`] will be called at,
+    /// at least 1.
+    pub fn new(coil_a: A, coil_b: B, tick_hz: u32) -> Self {
+        Self {
+            coil_a,
+            coil_b,
+            throttle: u16::MAX,
+            mode: StepMode::FullStep,
+            phase: 4,
+            position: 0,
+            tick_hz: tick_hz.max(1),
+            step_hz: 0,
+            max_speed: 0,
+            acceleration: 0,
+            speed: 0,
+            accumulator: 0,
+            direction: Direction::Forward,
+            motion: Motion::Idle,
+            idle_hold: None,
+            idle_ticks: 0,
+            reduced: true,
+        }
+    }
+
+    /// Sets the throttle the coils are driven at, applying it immediately if they are at full
+    /// current.
+    pub fn set_throttle(&mut self, throttle: u16) -> Result<(), Error> {
+        self.throttle = throttle;
+
+        if !self.reduced {
+            self.energize()?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the coil sequence used from the next step on. Switching to a coarser mode may
+    /// shift the rotor forward by up to one step to align with its sequence, e.g. switching
+    /// between [`StepMode::FullStep`] and [`StepMode::Wave`] shifts it by half a step.
+    pub fn set_step_mode(&mut self, mode: StepMode) {
+        self.mode = mode;
+
+        let (increment, offset) = mode.phase_alignment();
+        while self.phase % increment != offset {
+            self.phase = (self.phase + 1) % PHASES;
+            self.position = self.position.wrapping_add(1);
+        }
+    }
+
+    pub fn get_step_mode(&self) -> StepMode {
+        self.mode
+    }
+
+    /// Drops the coils to `hold_throttle` once no step has been taken for `after_ticks` calls
+    /// of [`Stepper::step_tick`] or [`Stepper::run`], to cut heating while stationary. A
+    /// `hold_throttle` of zero de-energizes the coils entirely.
+    ///
+    /// Full current is restored one tick before the next step is taken.
+    pub fn set_idle_hold(&mut self, after_ticks: u32, hold_throttle: u16) {
+        self.idle_hold = Some((after_ticks, hold_throttle));
+    }
+
+    /// Keeps the coils at full current while stationary. Coils that are already reduced are
+    /// restored ahead of the next step as usual.
+    pub fn disable_idle_hold(&mut self) {
+        self.idle_hold = None;
+    }
+
+    /// Sets the step rate used by [`Stepper::step_tick`], capped at one step per tick.
+    pub fn set_step_rate(&mut self, step_hz: u32) {
+        self.step_hz = step_hz.min(self.tick_hz);
+    }
+
+    /// Sets the speed in steps/s [`Stepper::run`] accelerates up to, capped at one step per tick.
+    pub fn set_max_speed(&mut self, steps_per_sec: u32) {
+        self.max_speed = steps_per_sec.min(self.tick_hz);
+    }
+
+    /// Sets the acceleration and deceleration of [`Stepper::run`] in steps/s². Zero disables
+    /// acceleration limiting, jumping straight to the max speed.
+    pub fn set_acceleration(&mut self, steps_per_sec2: u32) {
+        self.acceleration = steps_per_sec2;
+    }
+
+    /// The current speed of [`Stepper::run`] in steps/s
+    pub fn speed(&self) -> u32 {
+        (self.speed >> RATE_FRAC_BITS) as u32
+    }
+
+    /// Queues `steps` steps in `direction`.
+    pub fn move_steps(&mut self, direction: Direction, steps: u32) {
+        self.move_by(match direction {
+            Direction::Forward => i64::from(steps),
+            Direction::Reverse => -i64::from(steps),
+        });
+    }
+
+    /// Moves to the absolute `position`, see [`Stepper::position`], like AccelStepper's
+    /// `moveTo`. Replaces any move in progress; [`Stepper::run`] decelerates and turns around
+    /// first if the new target is behind the direction of travel.
+    pub fn move_to(&mut self, position: i64) {
+        self.move_by(position.wrapping_sub(self.position()));
+    }
+
+    /// Moves `steps` steps from the current position, negative moving in reverse, like
+    /// AccelStepper's `move`. Replaces any move in progress, as [`Stepper::move_to`] does.
+    pub fn move_by(&mut self, steps: i64) {
+        self.motion = match steps {
+            0 => Motion::Idle,
+            steps => Motion::Steps(steps),
+        };
+    }
+
+    /// The absolute position in steps of the current [`StepMode`] relative to the last
+    /// [`Stepper::set_zero`], counting every step taken in any mode, so changing the mode
+    /// rescales it, e.g. two half steps read as one full step.
+    ///
+    /// The counter wraps around on overflow, which at one million full steps per second takes
+    /// over 35,000 years.
+    pub fn position(&self) -> i64 {
+        self.position.div_euclid(self.phases_per_step())
+    }
+
+    /// Makes the current position the zero position.
+    pub fn set_zero(&mut self) {
+        self.position = 0;
+    }
+
+    /// Overwrites the current position, in steps of the current [`StepMode`], e.g. after
+    /// homing.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position.wrapping_mul(self.phases_per_step());
+    }
+
+    /// Signed number of steps left in the current move, positive is forward
+    pub fn distance_to_go(&self) -> i64 {
+        match self.motion {
+            Motion::Steps(steps) => steps,
+            _ => 0,
+        }
+    }
+
+    /// Steps continuously in `direction` until stopped.
+    pub fn run_continuous(&mut self, direction: Direction) {
+        self.motion = Motion::Continuous(direction);
+    }
+
+    /// Stops stepping immediately. The coils stay energized, holding the current position.
+    pub fn stop(&mut self) {
+        self.motion = Motion::Idle;
+        self.speed = 0;
+        self.accumulator = 0;
+    }
+
+    /// Stops stepping and de-energizes both coils, letting the rotor turn freely.
+    pub fn release(&mut self) -> Result<(), Error> {
+        self.stop();
+        self.coil_a.set(Command::Stop(StopMode::Coast))?;
+        self.coil_b.set(Command::Stop(StopMode::Coast))?;
+        self.reduced = true;
+
+        Ok(())
+    }
+
+    /// Stops stepping and reinitializes both coil drivers, see [`HBridgeDriver::reinit`]. The
+    /// position is kept.
+    pub fn reinit(&mut self) -> Result<(), Error> {
+        self.stop();
+        self.coil_a.reinit()?;
+        self.coil_b.reinit()?;
+        self.reduced = true;
+
+        Ok(())
+    }
+
+    pub fn is_moving(&self) -> bool {
+        !matches!(self.motion, Motion::Idle) || self.speed != 0
+    }
+
+    /// Advances the constant rate step generator by one tick, taking a step when one is due.
+    /// Intended to be called from a timer interrupt at `tick_hz`. Returns whether a step was
+    /// taken.
+    pub fn step_tick(&mut self) -> Result<bool, Error> {
+        if self.restore_from_idle()? {
+            return Ok(false);
+        }
+
+        let stepped = self.constant_rate_step()?;
+        self.idle_tick(stepped)?;
+
+        Ok(stepped)
+    }
+
+    /// Advances the acceleration limited step generator by one tick, taking a step when one
+    /// is due. Intended to be called from a timer interrupt at `tick_hz`. Returns whether a
+    /// step was taken.
+    pub fn run(&mut self) -> Result<bool, Error> {
+        if self.restore_from_idle()? {
+            return Ok(false);
+        }
+
+        let stepped = self.accelerated_step()?;
+        self.idle_tick(stepped)?;
+
+        Ok(stepped)
+    }
+
+    /// Takes a constant rate step if one is due
+    fn constant_rate_step(&mut self) -> Result<bool, Error> {
+        let direction = match self.motion {
+            Motion::Idle => return Ok(false),
+            Motion::Steps(steps) => direction_of(steps),
+            Motion::Continuous(direction) => direction,
+        };
+
+        if !self.advance(u64::from(self.step_hz) << RATE_FRAC_BITS) {
+            return Ok(false);
+        }
+
+        self.direction = direction;
+        self.step_motion()?;
+
+        Ok(true)
+    }
+
+    /// Takes an acceleration limited step if one is due
+    fn accelerated_step(&mut self) -> Result<bool, Error> {
+        let (wanted, remaining) = match self.motion {
+            Motion::Idle => (None, 0),
+            Motion::Steps(steps) => (Some(direction_of(steps)), steps.unsigned_abs()),
+            Motion::Continuous(direction) => (Some(direction), u64::MAX),
+        };
+
+        if self.speed == 0 {
+            match wanted {
+                Some(direction) => self.direction = direction,
+                None => return Ok(false),
+            }
+        }
+
+        let max_speed = u64::from(self.max_speed) << RATE_FRAC_BITS;
+        if self.acceleration == 0 {
+            self.speed = match wanted {
+                Some(direction) if direction == self.direction => max_speed,
+                _ => 0,
+            };
+        } else {
+            // Speed change per tick, never zero so slow accelerations still make progress
+            let delta = ((u64::from(self.acceleration) << RATE_FRAC_BITS) / u64::from(self.tick_hz)).max(1);
+
+            // Steps it takes to stop from the current speed, v² / 2a
+            let speed = self.speed >> RATE_FRAC_BITS;
+            let stopping = speed * speed / (2 * u64::from(self.acceleration));
+
+            if wanted != Some(self.direction) {
+                self.speed = self.speed.saturating_sub(delta);
+            } else if remaining <= stopping {
+                self.speed = self.speed.saturating_sub(delta).max(delta);
+            } else {
+                self.speed = (self.speed + delta).min(max_speed);
+            }
+        }
+
+        if self.speed == 0 || !self.advance(self.speed) {
+            return Ok(false);
+        }
+
+        self.step_motion()?;
+        if let Motion::Idle = self.motion {
+            self.speed = 0;
+        }
+
+        Ok(true)
+    }
+
+    /// Takes a single step in `direction` immediately.
+    pub fn step(&mut self, direction: Direction) -> Result<(), Error> {
+        let (increment, _) = self.mode.phase_alignment();
+        self.phase = match direction {
+            Direction::Forward => (self.phase + increment) % PHASES,
+            Direction::Reverse => (self.phase + PHASES - increment) % PHASES,
+        };
+        self.position = match direction {
+            Direction::Forward => self.position.wrapping_add(increment as i64),
+            Direction::Reverse => self.position.wrapping_sub(increment as i64),
+        };
+
+        self.energize()
+    }
+
+    /// Releases the underlying coil drivers.
+    pub fn free(self) -> (A, B) {
+        (self.coil_a, self.coil_b)
+    }
+
+    /// Eighths of a full step in a step of the current mode
+    fn phases_per_step(&self) -> i64 {
+        self.mode.phase_alignment().0 as i64
+    }
+
+    /// Restores full current ahead of a pending move if the coils were reduced or released.
+    /// Returns whether it did, in which case no step is taken this tick.
+    fn restore_from_idle(&mut self) -> Result<bool, Error> {
+        if !self.reduced || !self.is_moving() {
+            return Ok(false);
+        }
+
+        self.energize()?;
+        Ok(true)
+    }
+
+    /// Counts ticks without a step, dropping to the hold throttle once idle for long enough
+    fn idle_tick(&mut self, stepped: bool) -> Result<(), Error> {
+        if stepped {
+            self.idle_ticks = 0;
+            return Ok(());
+        }
+        self.idle_ticks = self.idle_ticks.saturating_add(1);
+
+        if let Some((after_ticks, hold_throttle)) = self.idle_hold
+            && !self.reduced
+            && self.idle_ticks >= after_ticks
+        {
+            self.apply_phase(hold_throttle)?;
+            self.reduced = true;
+        }
+
+        Ok(())
+    }
+
+    /// Bresenham style rate division of the tick, so rates that don't divide the tick rate
+    /// evenly still average out exactly. Returns whether a step is due.
+    fn advance(&mut self, rate: u64) -> bool {
+        let tick = u64::from(self.tick_hz) << RATE_FRAC_BITS;
+
+        self.accumulator += rate;
+        if self.accumulator < tick {
+            return false;
+        }
+        self.accumulator -= tick;
+
+        true
+    }
+
+    /// Steps in the direction of travel and accounts for it in the current motion
+    fn step_motion(&mut self) -> Result<(), Error> {
+        self.step(self.direction)?;
+
+        if let Motion::Steps(steps) = &mut self.motion {
+            *steps -= match self.direction {
+                Direction::Forward => 1,
+                Direction::Reverse => -1,
+            };
+
+            if *steps == 0 {
+                self.motion = Motion::Idle;
+                self.accumulator = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drives the coils to the current phase at full current
+    fn energize(&mut self) -> Result<(), Error> {
+        self.apply_phase(self.throttle)?;
+        self.reduced = false;
+
+        Ok(())
+    }
+
+    /// Drives the coils to the current phase, scaled to `throttle`
+    fn apply_phase(&mut self, throttle: u16) -> Result<(), Error> {
+        let (a, b) = match self.mode {
+            StepMode::MicroStep4 | StepMode::MicroStep8 => {
+                (sine(self.phase + PHASES / 4), sine(self.phase))
+            },
+            StepMode::FullStep | StepMode::Wave | StepMode::HalfStep => {
+                let (a, b) = HALF_STEP[self.phase / 4];
+                (i32::from(a) * 0xFFFF, i32::from(b) * 0xFFFF)
+            },
+        };
+
+        self.coil_a.set(coil_command(a, throttle))?;
+        self.coil_b.set(coil_command(b, throttle))?;
+
+        Ok(())
+    }
+}
+
+/// Full scale sine of `phase`, in eighths of a full step
+fn sine(phase: usize) -> i32 {
+    let quarter = PHASES / 4;
+    let phase = phase % PHASES;
+    let offset = phase % quarter;
+
+    match phase / quarter {
+        0 => i32::from(QUARTER_SINE[offset]),
+        1 => i32::from(QUARTER_SINE[quarter - offset]),
+        2 => -i32::from(QUARTER_SINE[offset]),
+        _ => -i32::from(QUARTER_SINE[quarter - offset]),
+    }
+}
+
+/// The direction of travel of a signed step count
+fn direction_of(steps: i64) -> Direction {
+    if steps < 0 { Direction::Reverse } else { Direction::Forward }
+}
+
+/// Maps a signed, full scale coil level to the `Command` driving it at `throttle`
+fn coil_command(level: i32, throttle: u16) -> Command {
+    let throttle = duty_from_fullscale(throttle, level.unsigned_abs() as u16);
+
+    match level.signum() {
+        1 => Command::Drive { direction: Direction::Forward, throttle },
+        -1 => Command::Drive { direction: Direction::Reverse, throttle },
+        _ => Command::Stop(StopMode::Coast),
+    }
+}
+
+/// A [`Stepper`] with an encoder on its shaft, detecting lost steps by comparing the
+/// commanded position against the measured position.
+///
+/// Positions are compared in steps of the current [`StepMode`], so `steps_per_rev` must be
+/// updated with [`ClosedLoopStepper::set_steps_per_rev`] if the mode is changed.
+pub struct ClosedLoopStepper<A, B, E>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+    E: EncoderReader,
+{
+    stepper: Stepper<A, B>,
+    encoder: E,
+    steps_per_rev: u32,
+    counts_per_rev: u32,
+    max_error: u32,
+    encoder_zero: i64,
+    lost_steps: bool,
+}
+
+impl<A, B, E> ClosedLoopStepper<A, B, E>
+where
+    A: HBridgeDriver,
+    B: HBridgeDriver,
+    E: EncoderReader,
+{
+
+    /// Pairs `stepper` with `encoder`, taking the current encoder count as the stepper's
+    /// current position.
+    ///
+    /// Lost steps are flagged once the measured position is more than `max_error` steps away
+    /// from the commanded position.
+    pub fn new(
+        stepper: Stepper<A, B>,
+        mut encoder: E,
+        steps_per_rev: u32,
+        counts_per_rev: u32,
+        max_error: u32,
+    ) -> Self {
+        let count = encoder.count();
+        let mut handle = Self {
+            stepper,
+            encoder,
+            steps_per_rev,
+            counts_per_rev: counts_per_rev.max(1),
+            max_error,
+            encoder_zero: count,
+            lost_steps: false,
+        };
+        handle.encoder_zero = count - handle.counts_from_steps(handle.stepper.position());
+
+        handle
+    }
+
+    /// The underlying stepper, used to command moves.
+    pub fn stepper(&mut self) -> &mut Stepper<A, B> {
+        &mut self.stepper
+    }
+
+    /// Changes the steps per revolution, e.g. after changing the stepper's [`StepMode`].
+    /// The lost steps flag is kept.
+    pub fn set_steps_per_rev(&mut self, steps_per_rev: u32) {
+        self.steps_per_rev = steps_per_rev;
+    }
+
+    /// The position measured by the encoder, in steps
+    pub fn measured_position(&mut self) -> i64 {
+        let counts = self.encoder.count() - self.encoder_zero;
+        let steps = i128::from(counts) * i128::from(self.steps_per_rev) / i128::from(self.counts_per_rev);
+
+        steps as i64
+    }
+
+    /// How far the commanded position is ahead of the measured position, in steps
+    pub fn position_error(&mut self) -> i64 {
+        self.stepper.position().wrapping_sub(self.measured_position())
+    }
+
+    /// Compares the commanded and measured positions, stopping the stepper and latching the
+    /// lost steps flag if they have diverged. Intended to be called regularly, e.g. alongside
+    /// [`Stepper::run`]. Returns whether steps were lost.
+    pub fn check(&mut self) -> bool {
+        if self.position_error().unsigned_abs() > u64::from(self.max_error) {
+            self.stepper.stop();
+            self.lost_steps = true;
+        }
+
+        self.lost_steps
+    }
+
+    /// Whether steps were lost since the flag was last cleared by [`ClosedLoopStepper::resync`]
+    pub fn lost_steps(&self) -> bool {
+        self.lost_steps
+    }
+
+    /// [`Fault::STALL`] while the lost steps flag is set
+    pub fn faults(&self) -> Fault {
+        if self.lost_steps { Fault::STALL } else { Fault::NONE }
+    }
+
+    /// Takes the measured position as the stepper's position and clears the lost steps flag,
+    /// after which moves continue from where the shaft actually is.
+    pub fn resync(&mut self) {
+        let measured = self.measured_position();
+        self.stepper.set_position(measured);
+        self.lost_steps = false;
+    }
+
+    /// Makes the current position the zero position of both the stepper and the encoder.
+    pub fn set_zero(&mut self) {
+        self.stepper.set_zero();
+        self.encoder_zero = self.encoder.count();
+    }
+
+    /// Releases the stepper and encoder.
+    pub fn free(self) -> (Stepper<A, B>, E) {
+        (self.stepper, self.encoder)
+    }
+
+    /// Converts a position in steps to encoder counts
+    fn counts_from_steps(&self, steps: i64) -> i64 {
+        let counts = i128::from(steps) * i128::from(self.counts_per_rev) / i128::from(self.steps_per_rev.max(1));
+
+        counts as i64
+    }
+}
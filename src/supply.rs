@@ -0,0 +1,123 @@
+use crate::{HBridgeDriver, Command, StopMode, Fault, Error};
+
+/// Under-voltage lockout for a motor driver, from supply voltage readings provided
+/// periodically by the user (e.g. an ADC on a divider across the motor supply).
+///
+/// When the supply drops below the brown-out threshold the motor coasts and a fault is
+/// latched, during which drive commands are turned into coasting. The fault can only be
+/// cleared once the supply has recovered above the recovery threshold, giving hysteresis
+/// against oscillating in and out of lockout as the load is removed and reapplied.
+pub struct UnderVoltageLockout<M>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    brownout_mv: u32,
+    recovery_mv: u32,
+    supply_mv: Option<u32>,
+    latched: bool,
+}
+
+impl<M> UnderVoltageLockout<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// `recovery_mv` is raised to `brownout_mv` if it is lower.
+    pub fn new(motor: M, brownout_mv: u32, recovery_mv: u32) -> Self {
+        Self {
+            motor,
+            brownout_mv,
+            recovery_mv: recovery_mv.max(brownout_mv),
+            supply_mv: None,
+            latched: false,
+        }
+    }
+
+    /// Feeds the latest supply voltage reading, locking out the motor on brown-out.
+    pub fn update_supply_mv(&mut self, supply_mv: u32) -> Result<(), Error> {
+        self.supply_mv = Some(supply_mv);
+
+        if supply_mv < self.brownout_mv && !self.latched {
+            self.latched = true;
+            self.motor.set(Command::Stop(StopMode::Coast))?;
+        }
+
+        Ok(())
+    }
+
+    /// The latest supply voltage reading, `None` before the first
+    pub fn supply_mv(&self) -> Option<u32> {
+        self.supply_mv
+    }
+
+    pub fn is_locked_out(&self) -> bool {
+        self.latched
+    }
+
+    /// [`Fault::UNDERVOLTAGE`] while locked out
+    pub fn faults(&self) -> Fault {
+        if self.latched { Fault::UNDERVOLTAGE } else { Fault::NONE }
+    }
+
+    /// Clears the lockout if the supply has recovered above the recovery threshold. Returns
+    /// whether the motor is free to drive again.
+    pub fn clear_faults(&mut self) -> bool {
+        if self.supply_mv.is_some_and(|supply_mv| supply_mv >= self.recovery_mv) {
+            self.latched = false;
+        }
+
+        !self.latched
+    }
+
+    pub fn set_thresholds(&mut self, brownout_mv: u32, recovery_mv: u32) {
+        self.brownout_mv = brownout_mv;
+        self.recovery_mv = recovery_mv.max(brownout_mv);
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M> HBridgeDriver for UnderVoltageLockout<M>
+where
+    M: HBridgeDriver,
+{
+    /// Passes `cmd` to the motor, coasting instead of driving while locked out and
+    /// rejecting the drive command with [`Error::Faulted`]
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd {
+            Command::Drive { .. } if self.latched => {
+                self.motor.set(Command::Stop(StopMode::Coast))?;
+                Err(Error::Faulted(self.faults()))
+            }
+            cmd => self.motor.set(cmd),
+        }
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Reinitializes the motor and clears the lockout if its condition has recovered
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()?;
+        self.clear_faults();
+
+        Ok(())
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.faults() | self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{HBridgeDriver, EncoderReader, VelocityEstimator, Command, StopMode, Pid, FULL_SCALE, Error};
+use crate::mixer::signed_command;
+
+/// Synchronizes a slave motor to a master, for two motors sharing one shaft or belt, so
+/// they share the load evenly instead of fighting each other.
+///
+/// The master is driven as usual, open loop or by its own controller. Each update, the slave
+/// is given the master's throttle as a feedforward plus a trim from `pid`, which regulates
+/// the slave's encoder rate to the master's. Both encoders must count the same way for the
+/// same motion.
+///
+/// Throttles are signed full scale, `-FULL_SCALE..=FULL_SCALE`.
+pub struct SpeedFollower<M, E>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+    motor: M,
+    encoder: E,
+    estimator: VelocityEstimator,
+    pid: Pid,
+    trim: i32,
+}
+
+impl<M, E> SpeedFollower<M, E>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+
+    /// Wraps the slave `motor` and its `encoder`. The trim is limited to a quarter of full
+    /// scale, see [`SpeedFollower::set_max_trim`].
+    pub fn new(motor: M, encoder: E, estimator: VelocityEstimator, mut pid: Pid) -> Self {
+        pid.set_output_limits(-FULL_SCALE / 4, FULL_SCALE / 4);
+
+        Self { motor, encoder, estimator, pid, trim: 0 }
+    }
+
+    /// Limits how far the slave's throttle may be trimmed from the master's
+    pub fn set_max_trim(&mut self, max_trim: u16) {
+        self.pid.set_output_limits(-i32::from(max_trim), i32::from(max_trim));
+    }
+
+    /// Reads the slave's encoder and drives it at the master's throttle plus the trim
+    /// matching its speed to `master_speed`, in counts/s. Must be called at the estimator's
+    /// update rate, e.g. with the throttle and speed from a master's [`Telemetry`].
+    ///
+    /// [`Telemetry`]: crate::Telemetry
+    pub fn update(&mut self, master_throttle: i32, master_speed: i32) -> Result<(), Error> {
+        let speed = self.estimator.update_from(&mut self.encoder);
+        self.trim = self.pid.update(master_speed, speed);
+
+        let throttle = master_throttle.saturating_add(self.trim).clamp(-FULL_SCALE, FULL_SCALE);
+        self.motor.set(signed_command(throttle))
+    }
+
+    /// Stops the slave using `stop_mode` and resets the trim. The master must be stopped
+    /// the same way.
+    pub fn stop(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.pid.reset();
+        self.trim = 0;
+        self.motor.set(Command::Stop(stop_mode))
+    }
+
+    /// The slave's latest speed estimate in counts/s
+    pub fn speed(&self) -> i32 {
+        self.estimator.velocity()
+    }
+
+    /// The trim last added to the master's throttle
+    pub fn trim(&self) -> i32 {
+        self.trim
+    }
+
+    pub fn pid(&mut self) -> &mut Pid {
+        &mut self.pid
+    }
+
+    /// Releases the slave motor and encoder.
+    pub fn free(self) -> (M, E) {
+        (self.motor, self.encoder)
+    }
+}
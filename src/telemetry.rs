@@ -0,0 +1,346 @@
+use crate::{HBridgeDriver, Command, StopMode, Fault, Error};
+use crate::mixer::signed_throttle;
+
+/// One cycle's worth of motor state, for streaming to a host or logging
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Telemetry {
+    pub timestamp_ms: u32,
+    /// What the control loop is regulating to, in its own units (e.g. counts/s or mA)
+    pub setpoint: i32,
+    /// Measured speed in encoder counts/s
+    pub speed: i32,
+    /// Applied throttle, negative is reverse
+    pub throttle: i32,
+    pub current_ma: u32,
+    pub faults: Fault,
+    /// PWM frequency of the enable output, zero if it hasn't been reported
+    pub pwm_frequency_hz: u32,
+}
+
+impl Telemetry {
+
+    /// Length of an encoded frame in bytes
+    pub const FRAME_LEN: usize = 2 + 1 + PAYLOAD_LEN + 2;
+
+    /// A frame for the throttle and faults of `motor`, the other fields are zero. The
+    /// controllers fill in the rest, see e.g. [`VelocityController::telemetry`].
+    ///
+    /// [`VelocityController::telemetry`]: crate::VelocityController::telemetry
+    pub fn from_driver<M>(motor: &M, timestamp_ms: u32) -> Self
+    where
+        M: HBridgeDriver + ?Sized,
+    {
+        Self {
+            timestamp_ms,
+            throttle: i32::from(motor.get_throttle()),
+            faults: motor.latched_faults(),
+            pwm_frequency_hz: motor.pwm_frequency_hz().unwrap_or(0),
+            ..Self::default()
+        }
+    }
+
+    /// Encodes a fixed size binary frame: the sync bytes `0xA5 0x5A`, the payload length,
+    /// the fields in declaration order as little endian, then a CRC-16/CCITT-FALSE of the
+    /// length and payload, little endian.
+    pub fn encode(&self) -> [u8; Self::FRAME_LEN] {
+        let mut frame = [0u8; Self::FRAME_LEN];
+        frame[..2].copy_from_slice(&SYNC);
+        frame[2] = PAYLOAD_LEN as u8;
+
+        let payload = &mut frame[3..3 + PAYLOAD_LEN];
+        payload[0..4].copy_from_slice(&self.timestamp_ms.to_le_bytes());
+        payload[4..8].copy_from_slice(&self.setpoint.to_le_bytes());
+        payload[8..12].copy_from_slice(&self.speed.to_le_bytes());
+        payload[12..16].copy_from_slice(&self.throttle.to_le_bytes());
+        payload[16..20].copy_from_slice(&self.current_ma.to_le_bytes());
+        payload[20] = self.faults.bits();
+        payload[21..25].copy_from_slice(&self.pwm_frequency_hz.to_le_bytes());
+
+        let crc = crc16(&frame[2..3 + PAYLOAD_LEN]);
+        frame[3 + PAYLOAD_LEN..].copy_from_slice(&crc.to_le_bytes());
+
+        frame
+    }
+
+    /// Decodes a frame produced by [`Telemetry::encode`], `None` if it is malformed or the
+    /// CRC doesn't match
+    pub fn decode(frame: &[u8]) -> Option<Self> {
+        if frame.len() != Self::FRAME_LEN || frame[..2] != SYNC || usize::from(frame[2]) != PAYLOAD_LEN {
+            return None;
+        }
+
+        let crc = u16::from_le_bytes([frame[3 + PAYLOAD_LEN], frame[4 + PAYLOAD_LEN]]);
+        if crc != crc16(&frame[2..3 + PAYLOAD_LEN]) {
+            return None;
+        }
+
+        let payload = &frame[3..3 + PAYLOAD_LEN];
+        let word = |at: usize| [payload[at], payload[at + 1], payload[at + 2], payload[at + 3]];
+
+        Some(Self {
+            timestamp_ms: u32::from_le_bytes(word(0)),
+            setpoint: i32::from_le_bytes(word(4)),
+            speed: i32::from_le_bytes(word(8)),
+            throttle: i32::from_le_bytes(word(12)),
+            current_ma: u32::from_le_bytes(word(16)),
+            faults: Fault::from_bits_truncate(payload[20]),
+            pwm_frequency_hz: u32::from_le_bytes(word(21)),
+        })
+    }
+}
+
+const SYNC: [u8; 2] = [0xA5, 0x5A];
+const PAYLOAD_LEN: usize = 25;
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+
+    for &byte in bytes {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+
+    crc
+}
+
+/// Somewhere [`Telemetry`] goes, e.g. a [`BlackBox`] or a serial port through a
+/// [`FrameEncoder`], so the control code doesn't depend on where. See
+/// [`ControlStep::control_step_into`].
+///
+/// [`ControlStep::control_step_into`]: crate::ControlStep::control_step_into
+pub trait TelemetrySink {
+    fn record(&mut self, frame: &Telemetry);
+}
+
+impl<T> TelemetrySink for &mut T
+where
+    T: TelemetrySink + ?Sized,
+{
+    fn record(&mut self, frame: &Telemetry) {
+        (**self).record(frame)
+    }
+}
+
+/// Encodes each record with [`Telemetry::encode`] and hands the frame to `write`, e.g. to
+/// queue it for a UART.
+pub struct FrameEncoder<F>
+where
+    F: FnMut(&[u8]),
+{
+    write: F,
+}
+
+impl<F> FrameEncoder<F>
+where
+    F: FnMut(&[u8]),
+{
+
+    pub fn new(write: F) -> Self {
+        Self { write }
+    }
+
+    /// Releases the writer.
+    pub fn free(self) -> F {
+        self.write
+    }
+}
+
+impl<F> TelemetrySink for FrameEncoder<F>
+where
+    F: FnMut(&[u8]),
+{
+    fn record(&mut self, frame: &Telemetry) {
+        (self.write)(&frame.encode())
+    }
+}
+
+/// A "black box" ring buffer of the last `N` [`Telemetry`] records, for post-mortem analysis
+/// after an incident such as a stall or crash.
+///
+/// Once full, each record overwrites the oldest. [`BlackBox::freeze`] stops recording so the
+/// lead-up to an incident is preserved until it has been dumped.
+pub struct BlackBox<const N: usize> {
+    records: [Telemetry; N],
+    /// Index the next record is written to
+    head: usize,
+    len: usize,
+    frozen: bool,
+}
+
+impl<const N: usize> BlackBox<N> {
+
+    pub fn new() -> Self {
+        Self { records: [Telemetry::default(); N], head: 0, len: 0, frozen: false }
+    }
+
+    /// Appends `telemetry`, unless frozen
+    pub fn record(&mut self, telemetry: Telemetry) {
+        if self.frozen || N == 0 {
+            return;
+        }
+
+        self.records[self.head] = telemetry;
+        self.head = (self.head + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// Records `cmd` being sent to `motor`, with the command's signed throttle as the
+    /// setpoint. Call after applying the command.
+    pub fn record_command<M>(&mut self, cmd: Command, motor: &M, timestamp_ms: u32)
+    where
+        M: HBridgeDriver + ?Sized,
+    {
+        let setpoint = signed_throttle(cmd);
+        let telemetry = Telemetry::from_driver(motor, timestamp_ms);
+
+        self.record(Telemetry {
+            setpoint,
+            throttle: telemetry.throttle * setpoint.signum(),
+            ..telemetry
+        });
+    }
+
+    /// Stops recording, keeping the current contents
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Discards every record
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// The records from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &Telemetry> {
+        let start = (self.head + N - self.len) % N.max(1);
+        (0..self.len).map(move |i| &self.records[(start + i) % N])
+    }
+}
+
+impl<const N: usize> TelemetrySink for BlackBox<N> {
+    fn record(&mut self, frame: &Telemetry) {
+        BlackBox::record(self, *frame)
+    }
+}
+
+impl<const N: usize> Default for BlackBox<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps a motor driver, remembering the last `N` commands requested of it so supervisory
+/// code and loggers can inspect them, whatever the wrapped driver made of them.
+///
+/// Once full, each command overwrites the oldest.
+pub struct CommandHistory<M, const N: usize>
+where
+    M: HBridgeDriver,
+{
+    motor: M,
+    commands: [Command; N],
+    /// Index the next command is written to
+    head: usize,
+    len: usize,
+}
+
+impl<M, const N: usize> CommandHistory<M, N>
+where
+    M: HBridgeDriver,
+{
+
+    pub fn new(motor: M) -> Self {
+        Self { motor, commands: [Command::Stop(StopMode::Coast); N], head: 0, len: 0 }
+    }
+
+    /// The command requested `age` commands ago, zero being the latest
+    pub fn get(&self, age: usize) -> Option<Command> {
+        if age >= self.len {
+            return None;
+        }
+
+        Some(self.commands[(self.head + N - 1 - age) % N])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forgets every command
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// The commands from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &Command> {
+        let start = (self.head + N - self.len) % N.max(1);
+        (0..self.len).map(move |i| &self.commands[(start + i) % N])
+    }
+
+    /// Releases the underlying motor driver.
+    pub fn free(self) -> M {
+        self.motor
+    }
+}
+
+impl<M, const N: usize> HBridgeDriver for CommandHistory<M, N>
+where
+    M: HBridgeDriver,
+{
+    /// Records `cmd`, then passes it to the motor
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        if N > 0 {
+            self.commands[self.head] = cmd;
+            self.head = (self.head + 1) % N;
+            self.len = (self.len + 1).min(N);
+        }
+
+        self.motor.set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    /// The latest command requested of this wrapper
+    fn last_command(&self) -> Option<Command> {
+        self.get(0)
+    }
+}
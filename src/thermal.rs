@@ -0,0 +1,139 @@
+use embedded_hal::digital;
+use core::convert::Infallible;
+
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, StopMode, Fault, Error};
+
+/// Over-temperature protection for a motor driver from an external digital input, such as
+/// a thermal switch or comparator output near the heatsink.
+///
+/// While the input is asserted the motor coasts and an over-temperature fault is latched,
+/// during which drive commands are turned into coasting. The fault can only be cleared once
+/// the input has been deasserted continuously for the recovery time, so the bridge has
+/// cooled somewhat rather than sitting right at the switch threshold.
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+pub struct OverTemperatureGuard<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::InputPin<Error = Infallible>,
+{
+    motor: M,
+    input: P,
+    active_high: bool,
+    recovery_ms: u32,
+    deasserted_since_ms: Option<u32>,
+    cooled: bool,
+    latched: bool,
+}
+
+impl<M, P> OverTemperatureGuard<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::InputPin<Error = Infallible>,
+{
+
+    /// `active_high` selects whether a high or low `input` signals over-temperature.
+    pub fn new(motor: M, input: P, active_high: bool, recovery: impl IntoMillis) -> Self {
+        Self {
+            motor,
+            input,
+            active_high,
+            recovery_ms: recovery.into_millis(),
+            deasserted_since_ms: None,
+            cooled: false,
+            latched: false,
+        }
+    }
+
+    /// Samples the input, locking out the motor while it is asserted. Must be called
+    /// regularly.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        let asserted = self.input.is_high()? == self.active_high;
+
+        if asserted {
+            self.deasserted_since_ms = None;
+            self.cooled = false;
+
+            if !self.latched {
+                self.latched = true;
+                self.motor.set(Command::Stop(StopMode::Coast))?;
+            }
+
+            return Ok(());
+        }
+
+        match self.deasserted_since_ms {
+            Some(since_ms) => self.cooled = now_ms.wrapping_sub(since_ms) >= self.recovery_ms,
+            None => self.deasserted_since_ms = Some(now_ms),
+        }
+
+        Ok(())
+    }
+
+    pub fn is_over_temperature(&self) -> bool {
+        self.latched
+    }
+
+    /// [`Fault::OVER_TEMPERATURE`] while latched
+    pub fn faults(&self) -> Fault {
+        if self.latched { Fault::OVER_TEMPERATURE } else { Fault::NONE }
+    }
+
+    /// Clears the over-temperature fault if the input has been deasserted for the recovery
+    /// time. Returns whether the motor is free to drive again.
+    pub fn clear_faults(&mut self) -> bool {
+        if self.cooled {
+            self.latched = false;
+        }
+
+        !self.latched
+    }
+
+    /// Releases the underlying motor driver and input pin.
+    pub fn free(self) -> (M, P) {
+        (self.motor, self.input)
+    }
+}
+
+impl<M, P> HBridgeDriver for OverTemperatureGuard<M, P>
+where
+    M: HBridgeDriver,
+    P: digital::InputPin<Error = Infallible>,
+{
+    /// Passes `cmd` to the motor, coasting instead of driving while over-temperature and
+    /// rejecting the drive command with [`Error::Faulted`]
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd {
+            Command::Drive { .. } if self.latched => {
+                self.motor.set(Command::Stop(StopMode::Coast))?;
+                Err(Error::Faulted(self.faults()))
+            }
+            cmd => self.motor.set(cmd),
+        }
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    /// Reinitializes the motor and clears the over-temperature fault if its condition has recovered
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()?;
+        self.clear_faults();
+
+        Ok(())
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.faults() | self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}
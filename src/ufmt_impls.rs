@@ -0,0 +1,62 @@
+//! `ufmt` formatting, for targets where `core::fmt` is too heavy.
+
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+use crate::{Command, Direction, Fault, StopMode, SolenoidState, throttle_percent};
+
+impl uDisplay for Direction {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self {
+            Direction::Forward => f.write_str("FWD"),
+            Direction::Reverse => f.write_str("REV"),
+        }
+    }
+}
+
+impl uDisplay for StopMode {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self {
+            StopMode::Brake => f.write_str("BRAKE"),
+            StopMode::Coast => f.write_str("COAST"),
+        }
+    }
+}
+
+impl uDisplay for Command {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self {
+            Command::Drive { direction, throttle } => {
+                uwrite!(f, "{} {}%", direction, throttle_percent(*throttle))
+            },
+            Command::Stop(stop_mode) => uwrite!(f, "{}", stop_mode),
+        }
+    }
+}
+
+impl uDisplay for Fault {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        if self.is_empty() {
+            return f.write_str("OK");
+        }
+
+        f.write_str("FAULT:")?;
+        for (i, code) in self.codes().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(code)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl uDisplay for SolenoidState {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self {
+            SolenoidState::Released => f.write_str("RELEASED"),
+            SolenoidState::PullIn { .. } => f.write_str("PULL-IN"),
+            SolenoidState::Holding => f.write_str("HOLDING"),
+        }
+    }
+}
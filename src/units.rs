@@ -0,0 +1,91 @@
+//! Thin integer newtypes for physical quantities, preventing unit mix-ups in the control APIs.
+
+
+use crate::{HBridgeDriver, EncoderReader, CurrentSensor, SpeedScale, TorqueController, VelocityController, UnderVoltageLockout, Error};
+
+/// A voltage in millivolts
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct MilliVolts(pub u32);
+
+/// A current in milliamps, positive is forward where a direction applies
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct MilliAmps(pub i32);
+
+/// An output shaft speed in revolutions per minute, positive is forward
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Rpm(pub i32);
+
+/// An encoder speed in counts (ticks) per second, positive is forward
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct TicksPerSec(pub i32);
+
+/// A speed unit a [`VelocityController`] can be commanded and read in
+pub trait SpeedUnit: Copy {
+    fn to_ticks_per_sec(self, scale: &SpeedScale) -> TicksPerSec;
+    fn from_ticks_per_sec(ticks: TicksPerSec, scale: &SpeedScale) -> Self;
+}
+
+impl SpeedUnit for TicksPerSec {
+    fn to_ticks_per_sec(self, _scale: &SpeedScale) -> TicksPerSec {
+        self
+    }
+
+    fn from_ticks_per_sec(ticks: TicksPerSec, _scale: &SpeedScale) -> Self {
+        ticks
+    }
+}
+
+impl SpeedUnit for Rpm {
+    fn to_ticks_per_sec(self, scale: &SpeedScale) -> TicksPerSec {
+        TicksPerSec(scale.from_rpm(self.0))
+    }
+
+    fn from_ticks_per_sec(ticks: TicksPerSec, scale: &SpeedScale) -> Self {
+        Rpm(scale.to_rpm(ticks.0))
+    }
+}
+
+impl<M, S> TorqueController<M, S>
+where
+    M: HBridgeDriver,
+    S: CurrentSensor,
+{
+
+    /// Sets the current setpoint, see [`TorqueController::set_torque_ma`].
+    pub fn set_torque(&mut self, torque: MilliAmps) {
+        self.set_torque_ma(torque.0);
+    }
+
+    pub fn get_torque(&self) -> MilliAmps {
+        MilliAmps(self.get_torque_ma())
+    }
+}
+
+impl<M, E, const N: usize> VelocityController<M, E, N>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+
+    /// Sets the target speed in any [`SpeedUnit`], see [`VelocityController::set_speed`].
+    pub fn set_velocity<U: SpeedUnit>(&mut self, speed: U) {
+        let ticks = speed.to_ticks_per_sec(&self.speed_scale());
+        self.set_speed(ticks.0);
+    }
+
+    /// The latest speed estimate in any [`SpeedUnit`]
+    pub fn velocity<U: SpeedUnit>(&self) -> U {
+        U::from_ticks_per_sec(TicksPerSec(self.speed()), &self.speed_scale())
+    }
+}
+
+impl<M> UnderVoltageLockout<M>
+where
+    M: HBridgeDriver,
+{
+
+    /// Feeds the latest supply voltage reading, see [`UnderVoltageLockout::update_supply_mv`].
+    pub fn update_supply(&mut self, supply: MilliVolts) -> Result<(), Error> {
+        self.update_supply_mv(supply.0)
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{HBridgeDriver, EncoderReader, VelocityEstimator, SpeedScale, Pid, AntiWindup, GainSchedule, Command, Direction, StopMode, Fault, Telemetry, Error};
+use crate::pid::slew_toward;
+
+/// Closed-loop speed controller for a motor with an encoder.
+///
+/// The speed is estimated with a [`VelocityEstimator`] and regulated by a [`Pid`] whose
+/// output is a signed throttle. The gains are taken from a [`GainSchedule`] at the current
+/// setpoint speed, use [`GainSchedule::fixed`] for a single set of gains.
+///
+/// Speeds are in encoder counts/s, or in output shaft RPM or milliradians/s once the
+/// encoder resolution and gearing are configured with [`VelocityController::set_speed_scale`].
+///
+/// Step changes of the target speed can optionally be ramped inside the loop, see
+/// [`VelocityController::set_setpoint_ramp`].
+///
+/// Gains, limits and ramping can be switched together at runtime with a
+/// [`VelocityProfile`], see [`VelocityController::switch_profile`].
+///
+/// [`VelocityController::update`] must be called at the fixed rate the estimator was
+/// created for and the gains were tuned at.
+pub struct VelocityController<M, E, const N: usize>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+    motor: M,
+    encoder: E,
+    estimator: VelocityEstimator,
+    pid: Pid,
+    schedule: GainSchedule<N>,
+    target: i32,
+    /// The target after ramping, what the loop actually regulates to
+    setpoint: i32,
+    ramp: Option<u32>,
+    scale: SpeedScale,
+    pending_profile: Option<VelocityProfile<N>>,
+    last_output: i32,
+    enabled: bool,
+    cruising: bool,
+}
+
+/// A named set of [`VelocityController`] tuning, switched between as a whole at runtime
+/// (e.g. "precision" and "fast travel")
+#[derive(Clone, Copy)]
+pub struct VelocityProfile<const N: usize> {
+    pub name: &'static str,
+    pub schedule: GainSchedule<N>,
+    pub anti_windup: AntiWindup,
+    /// See [`VelocityController::set_setpoint_ramp`]
+    pub setpoint_ramp: Option<u32>,
+    /// The largest throttle the loop may output
+    pub max_throttle: u16,
+}
+
+/// A fixed collection of `P` [`VelocityProfile`]s, looked up by name
+pub struct ProfileSet<const P: usize, const N: usize> {
+    profiles: [VelocityProfile<N>; P],
+}
+
+impl<const P: usize, const N: usize> ProfileSet<P, N> {
+
+    pub fn new(profiles: [VelocityProfile<N>; P]) -> Self {
+        Self { profiles }
+    }
+
+    /// The profile called `name`, if any
+    pub fn get(&self, name: &str) -> Option<&VelocityProfile<N>> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VelocityProfile<N>> {
+        self.profiles.iter()
+    }
+}
+
+impl<M, E, const N: usize> VelocityController<M, E, N>
+where
+    M: HBridgeDriver,
+    E: EncoderReader,
+{
+
+    /// Wraps `motor` and `encoder`. The loop is disabled until the first
+    /// [`VelocityController::set_speed`].
+    pub fn new(motor: M, encoder: E, estimator: VelocityEstimator, schedule: GainSchedule<N>) -> Self {
+        let mut pid = Pid::new(0, 0, 0);
+        pid.set_output_limits(-i32::from(u16::MAX), i32::from(u16::MAX));
+
+        Self {
+            motor,
+            encoder,
+            estimator,
+            pid,
+            schedule,
+            target: 0,
+            setpoint: 0,
+            ramp: None,
+            scale: SpeedScale::default(),
+            pending_profile: None,
+            last_output: 0,
+            enabled: false,
+            cruising: false,
+        }
+    }
+
+    /// Sets the target speed in encoder counts/s, positive is forward, and enables the loop.
+    pub fn set_speed(&mut self, counts_per_sec: i32) {
+        if !self.enabled {
+            self.pid.reset();
+            self.setpoint = self.estimator.velocity();
+        }
+
+        self.target = counts_per_sec;
+        self.enabled = true;
+        self.cruising = false;
+    }
+
+    /// Cruise control: holds the speed the motor is measured at now, until the next
+    /// [`VelocityController::set_speed`] or stop.
+    ///
+    /// If the loop was disabled, e.g. while the motor was driven open loop, it starts from
+    /// the motor's current throttle so the handover is bumpless.
+    pub fn engage_cruise(&mut self) {
+        let speed = self.speed();
+
+        if !self.enabled {
+            let throttle = i32::from(self.motor.get_throttle());
+            self.pid.reset();
+            self.pid.preload(if speed < 0 { -throttle } else { throttle });
+            self.enabled = true;
+        }
+
+        self.target = speed;
+        self.setpoint = speed;
+        self.cruising = true;
+    }
+
+    /// Whether cruise control is holding a speed
+    pub fn is_cruising(&self) -> bool {
+        self.cruising
+    }
+
+    pub fn get_speed_setpoint(&self) -> i32 {
+        self.target
+    }
+
+    pub fn set_speed_scale(&mut self, scale: SpeedScale) {
+        self.scale = scale;
+    }
+
+    pub fn speed_scale(&self) -> SpeedScale {
+        self.scale
+    }
+
+    /// Sets the target speed in output shaft RPM, see [`VelocityController::set_speed`].
+    pub fn set_speed_rpm(&mut self, rpm: i32) {
+        self.set_speed(self.scale.from_rpm(rpm));
+    }
+
+    /// Sets the target speed in output shaft milliradians/s, see
+    /// [`VelocityController::set_speed`].
+    pub fn set_speed_mrad_per_sec(&mut self, mrad_per_sec: i32) {
+        self.set_speed(self.scale.from_mrad_per_sec(mrad_per_sec));
+    }
+
+    /// The latest speed estimate in output shaft RPM
+    pub fn speed_rpm(&self) -> i32 {
+        self.scale.to_rpm(self.speed())
+    }
+
+    /// The latest speed estimate in output shaft milliradians/s
+    pub fn speed_mrad_per_sec(&self) -> i32 {
+        self.scale.to_mrad_per_sec(self.speed())
+    }
+
+    /// The setpoint the loop is currently regulating to, which lags the target speed while
+    /// ramping
+    pub fn get_ramped_setpoint(&self) -> i32 {
+        self.setpoint
+    }
+
+    /// Limits how fast the setpoint follows the target speed, in counts/s per update. This
+    /// keeps step changes of the target from saturating the integrator and overshooting.
+    /// `None` applies the target immediately.
+    pub fn set_setpoint_ramp(&mut self, max_step: Option<u32>) {
+        self.ramp = max_step;
+    }
+
+    /// The latest speed estimate in encoder counts/s
+    pub fn speed(&self) -> i32 {
+        self.estimator.velocity()
+    }
+
+    /// Disables the loop and stops the motor using `stop_mode`.
+    pub fn stop(&mut self, stop_mode: StopMode) -> Result<(), Error> {
+        self.enabled = false;
+        self.cruising = false;
+        self.target = 0;
+        self.setpoint = 0;
+        self.motor.set(Command::Stop(stop_mode))
+    }
+
+    pub fn set_gain_schedule(&mut self, schedule: GainSchedule<N>) {
+        self.schedule = schedule;
+    }
+
+    /// Switches to `profile` at the next safe point, when the loop's output is zero or
+    /// crosses zero, so the motor is never jerked by a change of gains or limits mid-drive.
+    /// While the loop is disabled the profile is applied immediately.
+    pub fn switch_profile(&mut self, profile: VelocityProfile<N>) {
+        if self.enabled {
+            self.pending_profile = Some(profile);
+        } else {
+            self.apply_profile(profile);
+        }
+    }
+
+    /// Whether a profile switch is waiting for a safe point
+    pub fn is_profile_pending(&self) -> bool {
+        self.pending_profile.is_some()
+    }
+
+    /// Reads the encoder and, if the loop is enabled, updates the throttle.
+    pub fn update(&mut self) -> Result<(), Error> {
+        let speed = self.estimator.update_from(&mut self.encoder);
+
+        if !self.enabled {
+            if let Some(profile) = self.pending_profile.take() {
+                self.apply_profile(profile);
+            }
+
+            return Ok(());
+        }
+
+        self.setpoint = match self.ramp {
+            Some(max_step) => slew_toward(self.setpoint, self.target, max_step),
+            None => self.target,
+        };
+
+        if let Some(gains) = self.schedule.gains_at(self.setpoint.unsigned_abs()) {
+            self.pid.set_gains(gains.kp, gains.ki, gains.kd);
+        }
+
+        let output = self.pid.update(self.setpoint, speed);
+        if (output == 0 || output.signum() != self.last_output.signum())
+            && let Some(profile) = self.pending_profile.take()
+        {
+            self.apply_profile(profile);
+        }
+        self.last_output = output;
+
+        let direction = if output < 0 { Direction::Reverse } else { Direction::Forward };
+        let throttle = output.unsigned_abs() as u16;
+
+        self.motor.set(Command::Drive { direction, throttle })
+    }
+
+    /// Faults latched by the motor driver, see [`HBridgeDriver::latched_faults`]
+    pub fn latched_faults(&self) -> Fault {
+        self.motor.latched_faults()
+    }
+
+    /// This cycle's setpoint, speed, signed throttle and motor faults
+    pub fn telemetry(&self, timestamp_ms: u32) -> Telemetry {
+        Telemetry {
+            setpoint: self.setpoint,
+            speed: self.speed(),
+            throttle: if self.enabled { self.last_output } else { 0 },
+            ..Telemetry::from_driver(&self.motor, timestamp_ms)
+        }
+    }
+
+    pub fn pid(&mut self) -> &mut Pid {
+        &mut self.pid
+    }
+
+    fn apply_profile(&mut self, profile: VelocityProfile<N>) {
+        self.schedule = profile.schedule;
+        self.ramp = profile.setpoint_ramp;
+        self.pid.set_anti_windup(profile.anti_windup);
+        self.pid.set_output_limits(-i32::from(profile.max_throttle), i32::from(profile.max_throttle));
+    }
+
+    /// The motor driver, e.g. to feed a wrapper such as a [`SpeedGovernor`]
+    ///
+    /// [`SpeedGovernor`]: crate::SpeedGovernor
+    pub fn motor_mut(&mut self) -> &mut M {
+        &mut self.motor
+    }
+
+    /// Releases the motor and encoder.
+    pub fn free(self) -> (M, E) {
+        (self.motor, self.encoder)
+    }
+}
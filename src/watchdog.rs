@@ -0,0 +1,162 @@
+use crate::{Clock, IntoMillis, HBridgeDriver, Command, StopMode, Fault, Error};
+
+/// Feeds (kicks) a hardware watchdog timer, e.g. an independent watchdog peripheral.
+pub trait WatchdogFeed {
+    fn feed(&mut self);
+}
+
+impl<T> WatchdogFeed for &mut T
+where
+    T: WatchdogFeed + ?Sized,
+{
+    fn feed(&mut self) {
+        (**self).feed()
+    }
+}
+
+/// Wraps a motor driver with a command timeout, feeding a hardware watchdog only while
+/// commands keep arriving in time and the motor has no latched faults.
+///
+/// If no command arrives within the timeout, or the motor latches a fault, the motor coasts
+/// and the watchdog is no longer fed, so a main loop that hangs, rather than just stops
+/// sending commands, is still caught by the watchdog resetting the MCU.
+///
+/// Through [`HBridgeDriver`], e.g. in a [`MotorBank`], commands carry no timestamp, so they
+/// restart the timeout from the next [`CommandWatchdog::poll`].
+///
+/// Time is read from the [`Clock`] passed in, which may wrap.
+///
+/// [`MotorBank`]: crate::MotorBank
+pub struct CommandWatchdog<M, W>
+where
+    M: HBridgeDriver,
+    W: WatchdogFeed,
+{
+    motor: M,
+    watchdog: W,
+    timeout_ms: u32,
+    last_command_ms: Option<u32>,
+    /// A command arrived through [`HBridgeDriver::set`], restarting the timeout at the next poll
+    restart: bool,
+    timed_out: bool,
+    /// Whether the motor was coasted for a latched fault
+    faulted: bool,
+}
+
+impl<M, W> CommandWatchdog<M, W>
+where
+    M: HBridgeDriver,
+    W: WatchdogFeed,
+{
+
+    /// Wraps `motor` and `watchdog`. The watchdog is not fed until the first command.
+    pub fn new(motor: M, watchdog: W, timeout: impl IntoMillis) -> Self {
+        Self {
+            motor,
+            watchdog,
+            timeout_ms: timeout.into_millis(),
+            last_command_ms: None,
+            restart: false,
+            timed_out: false,
+            faulted: false,
+        }
+    }
+
+    /// Sends `cmd` to the motor, restarting the command timeout.
+    pub fn set(&mut self, cmd: Command, clock: &impl Clock) -> Result<(), Error> {
+        let now_ms = clock.now_ms();
+        self.last_command_ms = Some(now_ms);
+        self.restart = false;
+        self.timed_out = false;
+        self.motor.set(cmd)
+    }
+
+    /// Checks the command timeout and the motor's latched faults, coasting the motor once
+    /// either fails, and feeds the watchdog only while both pass. Must be called more often
+    /// than the watchdog period. Returns whether the watchdog was fed.
+    pub fn poll(&mut self, clock: &impl Clock) -> Result<bool, Error> {
+        let now_ms = clock.now_ms();
+        if self.restart {
+            self.restart = false;
+            self.last_command_ms = Some(now_ms);
+        }
+
+        let Some(last_command_ms) = self.last_command_ms else {
+            return Ok(false);
+        };
+
+        if !self.motor.latched_faults().is_empty() {
+            if !self.faulted {
+                self.motor.set(Command::Stop(StopMode::Coast))?;
+                self.faulted = true;
+            }
+
+            return Ok(false);
+        }
+        self.faulted = false;
+
+        if now_ms.wrapping_sub(last_command_ms) > self.timeout_ms {
+            if !self.timed_out {
+                self.motor.set(Command::Stop(StopMode::Coast))?;
+                self.timed_out = true;
+            }
+
+            return Ok(false);
+        }
+
+        self.watchdog.feed();
+        Ok(true)
+    }
+
+    /// Whether the command timeout has expired since the last command
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// [`Fault::TIMEOUT`] while timed out, until the next command
+    pub fn faults(&self) -> Fault {
+        if self.timed_out { Fault::TIMEOUT } else { Fault::NONE }
+    }
+
+    pub fn set_timeout(&mut self, timeout: impl IntoMillis) {
+        self.timeout_ms = timeout.into_millis();
+    }
+
+    /// Releases the motor and watchdog.
+    pub fn free(self) -> (M, W) {
+        (self.motor, self.watchdog)
+    }
+}
+
+impl<M, W> HBridgeDriver for CommandWatchdog<M, W>
+where
+    M: HBridgeDriver,
+    W: WatchdogFeed,
+{
+    /// Sends `cmd` to the motor, restarting the command timeout from the next poll
+    fn set(&mut self, cmd: Command) -> Result<(), Error> {
+        self.restart = true;
+        self.timed_out = false;
+        self.motor.set(cmd)
+    }
+
+    fn get_throttle(&self) -> u16 {
+        self.motor.get_throttle()
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.motor.reinit()
+    }
+
+    fn latched_faults(&self) -> Fault {
+        self.faults() | self.motor.latched_faults()
+    }
+
+    fn pwm_frequency_hz(&self) -> Option<u32> {
+        self.motor.pwm_frequency_hz()
+    }
+
+    fn last_command(&self) -> Option<Command> {
+        self.motor.last_command()
+    }
+}